@@ -0,0 +1,138 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+use crate::arm64::{Instruction, MemoryOperand, Offset};
+use crate::pointer_auth::PointerAuth;
+use crate::structs::crash_info::{Arm64ThreadState, Register};
+
+lazy_static! {
+    // Address and instruction of a line of r2 disassembly: the instruction
+    // follows the address and the instruction bytes, up to the comments
+    static ref INSTRUCTION: Regex =
+        Regex::new(r"^[^0-9A-Za-z;]*0x([0-9A-Fa-f]+)\s+[0-9A-Fa-f]+\s+([a-z][^;]*)").unwrap();
+}
+
+// Registers known at the marked instruction of a frame
+#[derive(Clone, Copy)]
+pub enum FrameRegisters<'a> {
+    // Frame 0 of the crashing thread: the thread state at crash time
+    Crash(&'a Arm64ThreadState),
+    // A caller frame, from the frame chain: its call set lr to the return
+    // address of the frame. Its fp was saved on the stack, which the report
+    // doesn't hold, and its other registers are lost.
+    Caller { lr: u64 },
+}
+
+// Appends, to the marked instruction of an arm64 r2 listing, the registers
+// known there. In frame 0, the values at crash time of the registers it reads
+// (with their symbol or selector) and the effective address of its memory
+// access, flagged when it is the faulting address; at the call site of a
+// caller, the return address the call put in lr. The other instructions ran
+// with other values, or never ran: they are left as they are.
+pub fn annotate_arm64(
+    asm: &str,
+    registers: FrameRegisters,
+    pointer_auth: Option<PointerAuth>,
+    marked: u64,
+) -> String {
+    let strip = |value: u64| pointer_auth.map_or(value, |p| p.strip(value));
+
+    asm.lines()
+        .map(|line| match annotation(line, registers, &strip, marked) {
+            Some(annotation) => format!("{}  ; {}\n", line.trim_end(), annotation),
+            None => format!("{}\n", line),
+        })
+        .collect()
+}
+
+fn annotation(
+    line: &str,
+    registers: FrameRegisters,
+    strip: &dyn Fn(u64) -> u64,
+    marked: u64,
+) -> Option<String> {
+    let captures: Captures = INSTRUCTION.captures(line)?;
+    if u64::from_str_radix(captures.get(1)?.as_str(), 16).ok()? != marked {
+        return None;
+    }
+    let instruction: Instruction = Instruction::parse(captures.get(2)?.as_str())?;
+
+    let state: &Arm64ThreadState = match registers {
+        FrameRegisters::Crash(state) => state,
+        FrameRegisters::Caller { lr } => {
+            return instruction
+                .is_call()
+                .then(|| format!("lr={:#x} (return address)", lr));
+        }
+    };
+    let far: u64 = strip(state.far.value);
+
+    let mut parts: Vec<String> = instruction
+        .read_registers()
+        .iter()
+        .filter_map(|name| {
+            let (value, register) = register_value(state, name)?;
+            let mut part: String = format!("{}={:#x}", name, value);
+            if let Some(name) = register.objc_selector.as_ref().or(register.symbol.as_ref()) {
+                part.push_str(format!(" ({})", name).as_str());
+            }
+            Some(part)
+        })
+        .collect();
+
+    if let Some(address) = instruction
+        .memory_operand()
+        .and_then(|operand| effective_address(&operand, state, strip))
+    {
+        let mut part: String = format!("ea={:#x}", address);
+        if address == far {
+            part.push_str(" == far");
+        }
+        parts.push(part);
+    }
+
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+// Address accessed by a memory operand, from the register values at crash time
+pub fn effective_address(
+    operand: &MemoryOperand,
+    state: &Arm64ThreadState,
+    strip: &dyn Fn(u64) -> u64,
+) -> Option<u64> {
+    let base: u64 = strip(register_value(state, &operand.base)?.0);
+
+    let offset: i64 = match &operand.offset {
+        Offset::Immediate(offset) => *offset,
+        Offset::Register(index, extend, shift) => {
+            let value: u64 = register_value(state, index)?.0;
+            let extended: i64 = match extend.as_deref() {
+                Some("sxtw") => value as u32 as i32 as i64,
+                Some("uxtw") => value as u32 as i64,
+                _ => value as i64,
+            };
+            extended.wrapping_shl(*shift)
+        }
+    };
+
+    Some(base.wrapping_add_signed(offset))
+}
+
+// Value of a register at crash time. "w" registers are the low 32 bits of
+// the "x" ones.
+pub fn register_value<'a>(state: &'a Arm64ThreadState, name: &str) -> Option<(u64, &'a Register)> {
+    let register: &Register = match name {
+        "fp" | "x29" | "w29" => &state.fp,
+        "lr" | "x30" | "w30" => &state.lr,
+        "sp" | "wsp" => &state.sp,
+        _ => state.x.get(name.get(1..)?.parse::<usize>().ok()?)?,
+    };
+
+    let value: u64 = if name.starts_with('w') {
+        register.value & 0xffff_ffff
+    } else {
+        register.value
+    };
+
+    Some((value, register))
+}
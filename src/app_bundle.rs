@@ -0,0 +1,150 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// .ipa files unpacked so far
+static UNPACKED_IPAS: AtomicUsize = AtomicUsize::new(0);
+
+// An app given as an .ipa, an .xcarchive or an .app folder. An .ipa is
+// unpacked in a temporary folder that is removed when the bundle is dropped.
+pub struct AppBundle {
+    pub source: PathBuf,
+    // .app folders (Payload/*.app or Products/Applications/*.app)
+    pub apps: Vec<PathBuf>,
+    // .dSYM bundles of the app, its frameworks and its extensions
+    pub dsyms: Vec<PathBuf>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl AppBundle {
+    pub fn open(path: &Path) -> io::Result<AppBundle> {
+        let extension: String = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "ipa" => Self::open_ipa(path),
+            "xcarchive" => Ok(AppBundle {
+                source: path.to_path_buf(),
+                apps: list_dir(&path.join("Products/Applications"), "app"),
+                dsyms: list_dir(&path.join("dSYMs"), "dSYM"),
+                temp_dir: None,
+            }),
+            "app" => Ok(AppBundle {
+                source: path.to_path_buf(),
+                apps: vec![path.to_path_buf()],
+                dsyms: Vec::new(),
+                temp_dir: None,
+            }),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "expected an .ipa, an .xcarchive or an .app",
+            )),
+        }
+    }
+
+    // Unpacks the .ipa (a zip archive with a Payload folder) with `unzip`
+    fn open_ipa(path: &Path) -> io::Result<AppBundle> {
+        let stem: &str = path.file_stem().and_then(|s| s.to_str()).unwrap_or("app");
+        // Numbered, so that .ipa files sharing a name get their own folder
+        let n: usize = UNPACKED_IPAS.fetch_add(1, Ordering::Relaxed);
+        let temp_dir: PathBuf =
+            env::temp_dir().join(format!("assemblicate-{}-{}-{}", process::id(), n, stem));
+        fs::create_dir_all(&temp_dir)?;
+
+        let status: process::ExitStatus = Command::new("unzip")
+            .arg("-qq")
+            .arg("-o")
+            .arg(path)
+            .arg("-d")
+            .arg(&temp_dir)
+            .status()?;
+        if !status.success() {
+            let _ = fs::remove_dir_all(&temp_dir);
+            return Err(io::Error::other(format!("unzip failed ({})", status)));
+        }
+
+        Ok(AppBundle {
+            source: path.to_path_buf(),
+            apps: list_dir(&temp_dir.join("Payload"), "app"),
+            dsyms: list_dir(&temp_dir, "dSYM"),
+            temp_dir: Some(temp_dir),
+        })
+    }
+
+    // Folders to search for the binaries of the bundle. The parent of the .app
+    // folders comes first so that the `<App>.app/<App>` layout still applies.
+    pub fn search_roots(&self) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = Vec::new();
+
+        for app in &self.apps {
+            if let Some(parent) = app.parent() {
+                if !roots.iter().any(|r| r == parent) {
+                    roots.push(parent.to_path_buf());
+                }
+            }
+        }
+        roots.extend(self.dsyms.iter().cloned());
+
+        roots
+    }
+
+    // Describes the content of the bundle: main executables, embedded
+    // frameworks, app extensions and dSYMs
+    pub fn summary(&self) -> String {
+        let mut res: String = format!("APP BUNDLE: {}\n", self.source.display());
+
+        for app in &self.apps {
+            res.push_str(format!("  app:        {}\n", app.display()).as_str());
+            if let Some(executable) = bundle_executable(app) {
+                res.push_str(format!("  executable: {}\n", executable.display()).as_str());
+            }
+            for framework in list_dir(&app.join("Frameworks"), "framework") {
+                res.push_str(format!("  framework:  {}\n", framework.display()).as_str());
+            }
+            for extension in list_dir(&app.join("PlugIns"), "appex") {
+                res.push_str(format!("  extension:  {}\n", extension.display()).as_str());
+            }
+        }
+        for dsym in &self.dsyms {
+            res.push_str(format!("  dSYM:       {}\n", dsym.display()).as_str());
+        }
+
+        res
+    }
+}
+
+impl Drop for AppBundle {
+    fn drop(&mut self) {
+        if let Some(temp_dir) = &self.temp_dir {
+            let _ = fs::remove_dir_all(temp_dir);
+        }
+    }
+}
+
+// The main executable of an .app/.appex/.framework folder, named after it
+fn bundle_executable(bundle: &Path) -> Option<PathBuf> {
+    let executable: PathBuf = bundle.join(bundle.file_stem()?);
+
+    executable.is_file().then_some(executable)
+}
+
+// Entries of `dir` with the given extension, sorted by name
+fn list_dir(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == extension))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+
+    entries
+}
@@ -0,0 +1,271 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref REGISTER: Regex =
+        Regex::new(r"\b([xw](?:[0-9]|[12][0-9]|30)|fp|lr|sp|wsp)\b").unwrap();
+    // Memory operand: "[base]", "[base, #imm]" (also pre-indexed, with "!"),
+    // "[base, index{, extend #n}]"
+    static ref MEMORY_OPERAND: Regex = Regex::new(
+        r"\[([a-z0-9]+)(?:,\s*([xw][0-9]+|#?-?(?:0x[0-9a-f]+|[0-9]+))(?:,\s*(lsl|uxtw|sxtw|uxtx|sxtx)(?:\s*#?([0-9]+))?)?)?\]"
+    )
+    .unwrap();
+}
+
+// Instructions that only read their operands (compares, branches, prefetches)
+const READ_ONLY_MNEMONICS: &[&str] = &[
+    "cmp", "cmn", "tst", "ccmp", "ccmn", "fcmp", "cbz", "cbnz", "tbz", "tbnz", "b", "bl", "br",
+    "blr", "ret", "prfm",
+];
+// Loads writing two registers
+const PAIR_LOAD_MNEMONICS: &[&str] = &["ldp", "ldnp", "ldpsw", "ldxp", "ldaxp"];
+// Exclusive stores, writing their status register first
+const STORE_EXCLUSIVE_MNEMONICS: &[&str] = &[
+    "stxr", "stxrb", "stxrh", "stxp", "stlxr", "stlxrb", "stlxrh", "stlxp",
+];
+
+// An arm64 instruction, as r2 prints it ("ldr x8, [x0, 0x10]")
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub mnemonic: String,
+    pub operands: Vec<String>,
+}
+
+// Offset of a memory operand
+#[derive(Debug, Clone, PartialEq)]
+pub enum Offset {
+    Immediate(i64),
+    // Index register, extend ("lsl", "sxtw", ...) and shift amount
+    Register(String, Option<String>, u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryOperand {
+    pub base: String,
+    pub offset: Offset,
+}
+
+// Base register update of a pre-indexed ("[x0, 8]!") or post-indexed
+// ("[x0], 8") access: the base is incremented by `amount`, before the access
+// when pre-indexed, after it otherwise
+#[derive(Debug, Clone, PartialEq)]
+pub struct Writeback {
+    pub base: String,
+    pub amount: i64,
+    pub pre_indexed: bool,
+}
+
+impl Instruction {
+    pub fn parse(text: &str) -> Option<Instruction> {
+        let text: &str = text.trim();
+        let (mnemonic, operands) = text.split_once(' ').unwrap_or((text, ""));
+        if mnemonic.is_empty() {
+            return None;
+        }
+
+        Some(Instruction {
+            mnemonic: mnemonic.to_lowercase(),
+            operands: split_operands(operands),
+        })
+    }
+
+    // Number of leading operands the instruction writes
+    fn written_operands(&self) -> usize {
+        let mnemonic: &str = self.mnemonic.as_str();
+
+        if STORE_EXCLUSIVE_MNEMONICS.contains(&mnemonic) {
+            1
+        } else if self.is_store()
+            || READ_ONLY_MNEMONICS.contains(&mnemonic)
+            || mnemonic.starts_with("b.")
+            || mnemonic.starts_with("br")
+            || mnemonic.starts_with("blr")
+            || mnemonic.starts_with("ret")
+        {
+            0
+        } else if PAIR_LOAD_MNEMONICS.contains(&mnemonic) {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn is_store(&self) -> bool {
+        self.mnemonic.starts_with("st")
+    }
+
+    pub fn is_load(&self) -> bool {
+        self.mnemonic.starts_with("ld")
+    }
+
+    // Calls: bl, blr and their authenticated variants
+    pub fn is_call(&self) -> bool {
+        self.mnemonic == "bl" || self.mnemonic.starts_with("blr")
+    }
+
+    // Registers written, as named in the instruction, including the base
+    // register of accesses with writeback
+    pub fn written_registers(&self) -> Vec<&str> {
+        let mut written: Vec<&str> = self
+            .operands
+            .iter()
+            .take(self.written_operands())
+            .filter(|operand| !operand.starts_with('['))
+            .filter_map(|operand| REGISTER.find(operand).map(|r| r.as_str()))
+            .collect();
+        if self.writeback().is_some() {
+            let memory: Option<&String> = self.operands.iter().find(|o| o.starts_with('['));
+            if let Some(base) = memory.and_then(|operand| REGISTER.find(operand)) {
+                written.push(base.as_str());
+            }
+        }
+
+        written
+    }
+
+    // Registers a store writes to memory, in order ("stp x19, x20, [sp]"),
+    // without the status register of exclusive stores
+    pub fn stored_registers(&self) -> Vec<&str> {
+        if !self.is_store() {
+            return Vec::new();
+        }
+
+        self.operands
+            .iter()
+            .take_while(|operand| !operand.starts_with('['))
+            .skip(self.written_operands())
+            .filter_map(|operand| REGISTER.find(operand).map(|r| r.as_str()))
+            .collect()
+    }
+
+    // Registers read, as named in the instruction, in order of appearance.
+    // Destination registers are written, not read, except in memory operands.
+    pub fn read_registers(&self) -> Vec<&str> {
+        let written: usize = self.written_operands();
+        let mut read: Vec<&str> = Vec::new();
+
+        // "ret" returns to lr when it has no operand
+        if self.mnemonic.starts_with("ret") && self.operands.is_empty() {
+            read.push("lr");
+        }
+        for (n, operand) in self.operands.iter().enumerate() {
+            if n < written && !operand.starts_with('[') {
+                continue;
+            }
+            for register in REGISTER.find_iter(operand) {
+                if !read.contains(&register.as_str()) {
+                    read.push(register.as_str());
+                }
+            }
+        }
+
+        read
+    }
+
+    // Memory operand of a load or a store. Post-indexed accesses ("[x0], 8")
+    // access the base register as is.
+    pub fn memory_operand(&self) -> Option<MemoryOperand> {
+        let operand: &String = self.operands.iter().find(|o| o.starts_with('['))?;
+        let captures = MEMORY_OPERAND.captures(operand)?;
+
+        let offset: Offset = match captures.get(2).map(|m| m.as_str()) {
+            None => Offset::Immediate(0),
+            Some(index) if index.starts_with('x') || index.starts_with('w') => Offset::Register(
+                index.to_string(),
+                captures.get(3).map(|m| m.as_str().to_string()),
+                captures
+                    .get(4)
+                    .and_then(|m| m.as_str().parse().ok())
+                    .unwrap_or(0),
+            ),
+            Some(immediate) => Offset::Immediate(parse_immediate(immediate)?),
+        };
+
+        Some(MemoryOperand {
+            base: captures.get(1)?.as_str().to_string(),
+            offset,
+        })
+    }
+
+    // Base register update of the access, for pre- and post-indexed forms
+    pub fn writeback(&self) -> Option<Writeback> {
+        if !self.is_load() && !self.is_store() {
+            return None;
+        }
+        let position: usize = self.operands.iter().position(|o| o.starts_with('['))?;
+        let operand: MemoryOperand = self.memory_operand()?;
+
+        let (amount, pre_indexed) = if self.operands[position].ends_with('!') {
+            match operand.offset {
+                Offset::Immediate(offset) => (offset, true),
+                Offset::Register(..) => return None,
+            }
+        } else {
+            (parse_immediate(self.operands.get(position + 1)?)?, false)
+        };
+
+        Some(Writeback {
+            base: operand.base,
+            amount,
+            pre_indexed,
+        })
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.mnemonic, self.operands.join(", "))
+    }
+}
+
+// Name of the 64-bit register behind a register name: "w8" and "x8" are "x8",
+// "fp" is "x29", "lr" is "x30"
+pub fn canonical_register(name: &str) -> String {
+    match name {
+        "fp" | "w29" => "x29".to_string(),
+        "lr" | "w30" => "x30".to_string(),
+        "wsp" => "sp".to_string(),
+        _ => match name.strip_prefix('w') {
+            Some(number) => format!("x{}", number),
+            None => name.to_string(),
+        },
+    }
+}
+
+pub fn parse_immediate(immediate: &str) -> Option<i64> {
+    let immediate: &str = immediate.trim_start_matches('#');
+    let (negative, digits) = match immediate.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, immediate),
+    };
+    let value: i64 = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+// Splits operands on the commas outside of memory operands
+fn split_operands(operands: &str) -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+    let mut depth: i32 = 0;
+    let mut start: usize = 0;
+
+    for (i, c) in operands.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                res.push(operands[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !operands[start..].trim().is_empty() {
+        res.push(operands[start..].trim().to_string());
+    }
+
+    res
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::macho::{self, MachOSlice, MH_DSYM};
+use crate::structs::crash_info::UsedImage;
+
+// A Mach-O slice found on disk
+#[derive(Debug, Clone)]
+pub struct BinaryCandidate {
+    pub path: PathBuf,
+    pub slice: MachOSlice,
+}
+
+impl BinaryCandidate {
+    pub fn uuid(&self) -> &str {
+        self.slice.uuid.as_deref().unwrap_or("<no LC_UUID>")
+    }
+}
+
+// Result of looking up the binary of a used image
+pub enum Lookup<'a> {
+    // A slice with the same UUID as the crashed image
+    Found(&'a BinaryCandidate),
+    // Only a binary with the same name but a different UUID: its code may not
+    // be the one that crashed
+    Mismatch(&'a BinaryCandidate),
+    NotFound,
+}
+
+// Index of the Mach-O files available on disk, by LC_UUID and by file name.
+// dSYM companion files share the UUID of their binary but hold no code, they
+// are kept apart.
+pub struct BinaryLocator {
+    by_uuid: HashMap<String, BinaryCandidate>,
+    by_name: HashMap<String, Vec<BinaryCandidate>>,
+    dsyms: HashMap<String, BinaryCandidate>,
+}
+
+impl BinaryLocator {
+    pub fn new() -> Self {
+        Self {
+            by_uuid: HashMap::new(),
+            by_name: HashMap::new(),
+            dsyms: HashMap::new(),
+        }
+    }
+
+    // Recursively indexes every Mach-O file (each slice of fat files) under `root`.
+    // Symlinked directories are not followed.
+    pub fn index_dir(&mut self, root: &Path) {
+        let entries: fs::ReadDir = match fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries.flatten() {
+            let path: PathBuf = entry.path();
+            let file_type: fs::FileType = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+
+            if file_type.is_dir() {
+                self.index_dir(&path);
+            } else {
+                self.index_file(&path);
+            }
+        }
+    }
+
+    pub fn index_file(&mut self, path: &Path) {
+        let slices: Vec<MachOSlice> = match macho::read_slices(path) {
+            Ok(slices) => slices,
+            Err(_) => return,
+        };
+
+        let name: String = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => return,
+        };
+
+        for slice in slices {
+            let candidate: BinaryCandidate = BinaryCandidate {
+                path: path.to_path_buf(),
+                slice,
+            };
+            if candidate.slice.filetype == MH_DSYM {
+                if let Some(uuid) = &candidate.slice.uuid {
+                    self.dsyms.entry(uuid.clone()).or_insert(candidate);
+                }
+                continue;
+            }
+            if let Some(uuid) = &candidate.slice.uuid {
+                self.by_uuid
+                    .entry(uuid.clone())
+                    .or_insert(candidate.clone());
+            }
+            self.by_name
+                .entry(name.clone())
+                .or_default()
+                .push(candidate);
+        }
+    }
+
+    // The dSYM companion file of a used image, matched by UUID
+    pub fn dsym(&self, image: &UsedImage) -> Option<&BinaryCandidate> {
+        self.dsyms.get(&image.uuid.to_lowercase())
+    }
+
+    // The other slices of the file holding `candidate`
+    pub fn other_slices(&self, candidate: &BinaryCandidate) -> Vec<&BinaryCandidate> {
+        candidate
+            .path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|name| self.by_name.get(name))
+            .map(|candidates| {
+                candidates
+                    .iter()
+                    .filter(|c| c.path == candidate.path && c.slice.offset != candidate.slice.offset)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Looks up the binary of a used image by UUID, falling back to a name match
+    // (preferring the same architecture) that is reported as a mismatch.
+    pub fn lookup(&self, image: &UsedImage) -> Lookup<'_> {
+        if let Some(candidate) = self.by_uuid.get(&image.uuid.to_lowercase()) {
+            return Lookup::Found(candidate);
+        }
+
+        let candidates: &Vec<BinaryCandidate> =
+            match image.name.as_ref().and_then(|name| self.by_name.get(name)) {
+                Some(candidates) => candidates,
+                None => return Lookup::NotFound,
+            };
+
+        match candidates
+            .iter()
+            .find(|c| Some(&c.slice.arch) == image.arch.as_ref())
+            .or(candidates.first())
+        {
+            Some(candidate) => Lookup::Mismatch(candidate),
+            None => Lookup::NotFound,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use r2pipe::R2Pipe;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::dyld_cache::CacheMapping;
+
+// An instruction decoded from the bytes of the report
+pub struct StreamInstruction {
+    pub address: u64,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+// Decodes the standard base64 alphabet, padding optional, as the
+// instructionByteStream fields are written
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut res: Vec<u8> = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+
+    for c in text.trim().trim_end_matches('=').bytes() {
+        let value: u32 = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        } as u32;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(res)
+}
+
+pub fn format_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Disassembles the `size` bytes at `address`
+pub fn disassemble(r2: &mut R2Pipe, address: u64, size: usize) -> Option<Vec<StreamInstruction>> {
+    let ops: serde_json::Value = r2.cmdj(format!("pDj {} @ {:#x}", size, address).as_str()).ok()?;
+
+    ops.as_array()?
+        .iter()
+        .map(|op| {
+            let bytes: &str = op["bytes"].as_str()?;
+            Some(StreamInstruction {
+                address: op["offset"].as_u64()?,
+                bytes: (0..bytes.len() / 2)
+                    .filter_map(|n| u8::from_str_radix(&bytes[n * 2..n * 2 + 2], 16).ok())
+                    .collect(),
+                text: op["disasm"].as_str().unwrap_or("invalid").to_string(),
+            })
+        })
+        .collect()
+}
+
+// Disassembles bytes given on their own (the ones read from disk)
+pub fn disassemble_bytes(r2: &mut R2Pipe, bytes: &[u8]) -> String {
+    r2.cmd(format!("pad {}", format_bytes(bytes)).as_str())
+        .map(|text| text.trim().replace('\n', "; "))
+        .unwrap_or_default()
+}
+
+// Reads the bytes a dyld_shared_cache file holds at an unslid address
+pub fn read_mapped(path: &Path, mappings: &[CacheMapping], address: u64, size: usize) -> io::Result<Vec<u8>> {
+    let mapping: &CacheMapping = mappings
+        .iter()
+        .find(|m| address >= m.address && address + size as u64 <= m.address + m.size)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address not mapped by the file"))?;
+
+    let mut file: File = File::open(path)?;
+    file.seek(SeekFrom::Start(mapping.file_offset + address - mapping.address))?;
+    let mut bytes: Vec<u8> = vec![0; size];
+    file.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
@@ -0,0 +1,213 @@
+use r2pipe::R2Pipe;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Graphviz program rendering the DOT files
+const DOT_PROGRAM: &str = "dot";
+
+// Colors of the block holding the marked instruction and of the path to it
+const MARKED_BLOCK_COLOR: &str = "#ffb3b3";
+const PATH_BLOCK_COLOR: &str = "#fff2b3";
+const PATH_EDGE_COLOR: &str = "#d62728";
+
+// A basic block of the function, with its disassembly
+struct Block {
+    address: u64,
+    end: u64,
+    jump: Option<u64>,
+    fail: Option<u64>,
+    lines: Vec<String>,
+}
+
+// Files a graph was written to
+pub struct CfgFiles {
+    pub dot: PathBuf,
+    // None when Graphviz is not installed
+    pub svg: Option<PathBuf>,
+}
+
+// Control flow graph of the function containing `marked`, in the DOT
+// language. The block holding the marked instruction is filled in red and
+// flagged with `label`; the blocks and edges of the shortest path from the
+// entry block to it in yellow and red.
+pub fn dot(r2: &mut R2Pipe, function_start: Option<u64>, marked: u64, label: &str) -> Option<String> {
+    // Code mapped from a dyld_shared_cache is not analyzed up front
+    if let Some(start) = function_start {
+        r2.cmd(format!("af @ {:#x}", start).as_str()).ok()?;
+    }
+
+    let info: serde_json::Value = r2.cmdj(format!("afij @ {:#x}", marked).as_str()).ok()?;
+    let start: u64 = info.get(0)?["offset"].as_u64()?;
+    let name: String = info.get(0)?["name"].as_str().unwrap_or("").to_string();
+
+    let ops: serde_json::Value = r2.cmdj(format!("pdfj @ {:#x}", start).as_str()).ok()?;
+    let ops: Vec<(u64, String)> = ops["ops"]
+        .as_array()?
+        .iter()
+        .filter_map(|op| {
+            let text: &str = op["disasm"].as_str().or(op["opcode"].as_str())?;
+            let address: u64 = op["offset"].as_u64()?;
+            let line: String = if address == marked {
+                format!("{:#x}  {}  {}", address, text, label)
+            } else {
+                format!("{:#x}  {}", address, text)
+            };
+            Some((address, line))
+        })
+        .collect();
+
+    let blocks: serde_json::Value = r2.cmdj(format!("afbj @ {:#x}", start).as_str()).ok()?;
+    let blocks: Vec<Block> = blocks
+        .as_array()?
+        .iter()
+        .filter_map(|block| {
+            let address: u64 = block["addr"].as_u64()?;
+            let end: u64 = address + block["size"].as_u64()?;
+            Some(Block {
+                address,
+                end,
+                jump: block["jump"].as_u64(),
+                fail: block["fail"].as_u64(),
+                lines: ops
+                    .iter()
+                    .filter(|(a, _)| address <= *a && *a < end)
+                    .map(|(_, line)| line.clone())
+                    .collect(),
+            })
+        })
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let marked_block: Option<u64> = blocks
+        .iter()
+        .find(|b| b.address <= marked && marked < b.end)
+        .map(|b| b.address);
+    let path: Vec<u64> = marked_block
+        .and_then(|target| shortest_path(&blocks, start, target))
+        .unwrap_or_default();
+    let path_edges: HashSet<(u64, u64)> = path.windows(2).map(|w| (w[0], w[1])).collect();
+
+    let mut res: String = format!("digraph \"{}\" {{\n", escape(&name));
+    res.push_str("    node [shape=box fontname=\"Courier\" fontsize=10 style=filled fillcolor=white];\n");
+    for block in &blocks {
+        let fill: &str = if Some(block.address) == marked_block {
+            MARKED_BLOCK_COLOR
+        } else if path.contains(&block.address) {
+            PATH_BLOCK_COLOR
+        } else {
+            "white"
+        };
+        // Left-justified lines
+        let label: String = block
+            .lines
+            .iter()
+            .map(|line| format!("{}\\l", escape(line)))
+            .collect();
+        res.push_str(
+            format!(
+                "    \"{:#x}\" [label=\"{}\" fillcolor=\"{}\"];\n",
+                block.address, label, fill
+            )
+            .as_str(),
+        );
+    }
+    for block in &blocks {
+        let successors = [(block.jump, "jump"), (block.fail, "fail")];
+        for (successor, kind) in successors {
+            let successor: u64 = match successor {
+                Some(successor) if blocks.iter().any(|b| b.address == successor) => successor,
+                _ => continue,
+            };
+            // Conditional branches: green when taken, grey when falling
+            // through; unconditional ones in blue
+            let mut color: &str = match (kind, block.fail.is_some()) {
+                ("jump", true) => "#2ca02c",
+                ("fail", _) => "#7f7f7f",
+                _ => "#1f77b4",
+            };
+            let mut width: u32 = 1;
+            if path_edges.contains(&(block.address, successor)) {
+                color = PATH_EDGE_COLOR;
+                width = 3;
+            }
+            res.push_str(
+                format!(
+                    "    \"{:#x}\" -> \"{:#x}\" [color=\"{}\" penwidth={}];\n",
+                    block.address, successor, color, width
+                )
+                .as_str(),
+            );
+        }
+    }
+    res.push_str("}\n");
+
+    Some(res)
+}
+
+// Writes a graph to `<path>.dot` and, when Graphviz is installed, renders it
+// to `<path>.svg`
+pub fn write(dot: &str, path: &Path) -> io::Result<CfgFiles> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let dot_path: PathBuf = path.with_extension("dot");
+    File::create(&dot_path)?.write_all(dot.as_bytes())?;
+
+    let svg_path: PathBuf = path.with_extension("svg");
+    let svg: Option<PathBuf> = match Command::new(DOT_PROGRAM)
+        .arg("-Tsvg")
+        .arg(&dot_path)
+        .arg("-o")
+        .arg(&svg_path)
+        .status()
+    {
+        Ok(status) if status.success() => Some(svg_path),
+        Ok(status) => {
+            println!("{} failed on {}: {}", DOT_PROGRAM, dot_path.display(), status);
+            None
+        }
+        Err(_) => None,
+    };
+
+    Ok(CfgFiles { dot: dot_path, svg })
+}
+
+// Blocks from the entry to the target, following the fewest edges
+fn shortest_path(blocks: &[Block], entry: u64, target: u64) -> Option<Vec<u64>> {
+    let mut previous: HashMap<u64, u64> = HashMap::new();
+    let mut queue: VecDeque<u64> = VecDeque::from([entry]);
+    let mut seen: HashSet<u64> = HashSet::from([entry]);
+
+    while let Some(address) = queue.pop_front() {
+        if address == target {
+            let mut path: Vec<u64> = vec![target];
+            while let Some(from) = previous.get(path.last()?) {
+                path.push(*from);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        let block: &Block = match blocks.iter().find(|b| b.address == address) {
+            Some(block) => block,
+            None => continue,
+        };
+        for successor in [block.jump, block.fail].into_iter().flatten() {
+            if seen.insert(successor) {
+                previous.insert(successor, address);
+                queue.push_back(successor);
+            }
+        }
+    }
+
+    None
+}
+
+// Escapes a string for a quoted DOT id or label
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,299 @@
+use std::env;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use crate::decompiler;
+
+// Config file read from the current directory when --config is not given
+pub const DEFAULT_CONFIG_FILE: &str = "assemblicate.conf";
+
+// Options from the command line and the config file
+#[derive(Debug, Default)]
+pub struct Config {
+    pub report_path: String,
+    // Apps given as .ipa, .xcarchive or .app, searched before the search paths
+    pub apps: Vec<PathBuf>,
+    // Roots searched, in order and recursively, for app and system binaries
+    pub search_paths: Vec<PathBuf>,
+    // Directories holding one symbol directory per OS build
+    pub symbol_stores: Vec<PathBuf>,
+    // Main files of dyld_shared_caches, subcaches are found next to them
+    pub dyld_caches: Vec<PathBuf>,
+    // Program reading the DWARF of dSYM files (llvm-symbolizer by default)
+    pub symbolizer: Option<String>,
+    // Interleave source lines, from the dSYM line tables, with the disassembly
+    pub interleave_source: bool,
+    // Print Swift and C++ names as they are in the binaries
+    pub keep_mangled: bool,
+    // Programs demangling Swift and C++ names (swift-demangle and c++filt by default)
+    pub swift_demangler: Option<String>,
+    pub cxx_demangler: Option<String>,
+    // Instructions shown before and after the marked instruction of each
+    // frame, instead of the code from the start of the function
+    pub window: Option<u32>,
+    // Trace back where the register used by the crashing instruction comes from
+    pub provenance: bool,
+    // Decompiler shown instead of the assembly of each frame: "auto" or the
+    // r2 command of one (pdg for r2ghidra, pdd for r2dec, pdc)
+    pub decompiler: Option<String>,
+    // Export the control flow graph of the function of each frame
+    pub cfg: bool,
+    // Threads rendered besides the crashing one, and the ones of them that are
+    // disassembled
+    pub threads: Option<ThreadSelection>,
+    pub disassemble_threads: Option<ThreadSelection>,
+}
+
+// Threads chosen by index, as in the report
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThreadSelection {
+    All,
+    Indices(Vec<usize>),
+}
+
+impl ThreadSelection {
+    // "all" or a comma separated list of indices ("0,3,5")
+    fn parse(value: &str) -> Option<ThreadSelection> {
+        if value == "all" {
+            return Some(ThreadSelection::All);
+        }
+
+        value
+            .split(',')
+            .map(|index| index.trim().parse().ok())
+            .collect::<Option<Vec<usize>>>()
+            .map(ThreadSelection::Indices)
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        match self {
+            ThreadSelection::All => true,
+            ThreadSelection::Indices(indices) => indices.contains(&index),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Usage(String),
+    File { path: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Usage(message) => write!(f, "{}", message),
+            ConfigError::File { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl Config {
+    // Builds the configuration from the command line arguments (without the
+    // program name). Paths given on the command line come before the ones of
+    // the config file.
+    pub fn from_args(args: &[String]) -> Result<Config, ConfigError> {
+        let mut config: Config = Config::default();
+        let mut config_file: Option<String> = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-s" | "--search-path" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    config.search_paths.push(expand_home(value));
+                }
+                "-a" | "--app" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    config.apps.push(expand_home(value));
+                }
+                "-S" | "--symbol-store" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    config.symbol_stores.push(expand_home(value));
+                }
+                "-d" | "--dyld-cache" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    config.dyld_caches.push(expand_home(value));
+                }
+                "--source" => config.interleave_source = true,
+                "--mangled" => config.keep_mangled = true,
+                "--provenance" => config.provenance = true,
+                "--cfg" => config.cfg = true,
+                "-t" | "--threads" | "--disassemble-threads" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    let selection: ThreadSelection = ThreadSelection::parse(value).ok_or_else(|| {
+                        ConfigError::Usage(format!("Invalid thread list for {}: {}", arg, value))
+                    })?;
+                    if arg == "--disassemble-threads" {
+                        config.disassemble_threads = Some(selection);
+                    } else {
+                        config.threads = Some(selection);
+                    }
+                }
+                "--decompile" => {
+                    config.decompiler.get_or_insert(decompiler::AUTO.to_string());
+                }
+                "--decompiler" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    if !decompiler::is_known(value) {
+                        return Err(ConfigError::Usage(format!("Unknown decompiler {}", value)));
+                    }
+                    config.decompiler = Some(value.clone());
+                }
+                "-w" | "--window" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    config.window = Some(value.parse().map_err(|_| {
+                        ConfigError::Usage(format!("Invalid instruction count for {}: {}", arg, value))
+                    })?);
+                }
+                "-c" | "--config" => {
+                    let value: &String = args.next().ok_or_else(|| missing_value(arg))?;
+                    config_file = Some(value.clone());
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(ConfigError::Usage(format!("Unknown option {}", arg)));
+                }
+                _ if config.report_path.is_empty() => config.report_path = arg.clone(),
+                _ => {
+                    return Err(ConfigError::Usage(format!("Unexpected argument {}", arg)));
+                }
+            }
+        }
+
+        if config.report_path.is_empty() {
+            return Err(ConfigError::Usage(String::from(
+                "Missing crash report path",
+            )));
+        }
+
+        match config_file {
+            Some(path) => config.load_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                config.load_file(DEFAULT_CONFIG_FILE)?
+            }
+            None => {}
+        }
+
+        Ok(config)
+    }
+
+    // Reads a config file made of `key = value` lines. Empty lines and lines
+    // starting with '#' are ignored. Supported keys:
+    //   app = <ipa|xcarchive|app>  (may be repeated)
+    //   search_path = <dir>    (may be repeated)
+    //   symbol_store = <dir>   (may be repeated)
+    //   dyld_cache = <file>    (may be repeated)
+    //   symbolizer = <program>
+    //   interleave_source = true|false
+    //   keep_mangled = true|false
+    //   swift_demangler = <program>
+    //   cxx_demangler = <program>
+    //   window = <instructions>
+    //   provenance = true|false
+    //   decompiler = auto|pdg|pdd|pdc
+    //   cfg = true|false
+    //   threads = all|<index>,<index>...
+    //   disassemble_threads = all|<index>,<index>...
+    fn load_file(&mut self, path: &str) -> Result<(), ConfigError> {
+        let content: String = read_to_string(path).map_err(|e| ConfigError::File {
+            path: path.to_string(),
+            message: e.to_string(),
+        })?;
+
+        for (n, line) in content.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    return Err(ConfigError::File {
+                        path: path.to_string(),
+                        message: format!("line {}: expected `key = value`", n + 1),
+                    })
+                }
+            };
+
+            match key {
+                "app" => self.apps.push(expand_home(value)),
+                "search_path" => self.search_paths.push(expand_home(value)),
+                "symbol_store" => self.symbol_stores.push(expand_home(value)),
+                "dyld_cache" => self.dyld_caches.push(expand_home(value)),
+                "symbolizer" => self.symbolizer = Some(value.to_string()),
+                "interleave_source" => {
+                    self.interleave_source |= parse_bool(path, n + 1, value)?
+                }
+                "keep_mangled" => self.keep_mangled |= parse_bool(path, n + 1, value)?,
+                "provenance" => self.provenance |= parse_bool(path, n + 1, value)?,
+                "cfg" => self.cfg |= parse_bool(path, n + 1, value)?,
+                "threads" | "disassemble_threads" => {
+                    let selection: ThreadSelection =
+                        ThreadSelection::parse(value).ok_or_else(|| ConfigError::File {
+                            path: path.to_string(),
+                            message: format!("line {}: expected `all` or thread indices, got `{}`", n + 1, value),
+                        })?;
+                    // The command line wins
+                    if key == "threads" {
+                        self.threads.get_or_insert(selection);
+                    } else {
+                        self.disassemble_threads.get_or_insert(selection);
+                    }
+                }
+                "decompiler" => {
+                    if !decompiler::is_known(value) {
+                        return Err(ConfigError::File {
+                            path: path.to_string(),
+                            message: format!("line {}: unknown decompiler `{}`", n + 1, value),
+                        });
+                    }
+                    // The command line wins
+                    self.decompiler.get_or_insert(value.to_string());
+                }
+                "swift_demangler" => self.swift_demangler = Some(value.to_string()),
+                "cxx_demangler" => self.cxx_demangler = Some(value.to_string()),
+                "window" => {
+                    let window: u32 = value.parse().map_err(|_| ConfigError::File {
+                        path: path.to_string(),
+                        message: format!("line {}: expected an instruction count, got `{}`", n + 1, value),
+                    })?;
+                    // The command line wins
+                    self.window.get_or_insert(window);
+                }
+                _ => {
+                    return Err(ConfigError::File {
+                        path: path.to_string(),
+                        message: format!("line {}: unknown key `{}`", n + 1, key),
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_bool(path: &str, line: usize, value: &str) -> Result<bool, ConfigError> {
+    match value {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(ConfigError::File {
+            path: path.to_string(),
+            message: format!("line {}: expected true or false, got `{}`", line, value),
+        }),
+    }
+}
+
+fn missing_value(option: &str) -> ConfigError {
+    ConfigError::Usage(format!("Missing value for {}", option))
+}
+
+// Expands a leading "~/" to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
@@ -1,99 +1,597 @@
 use lazy_static::lazy_static;
 use r2pipe::R2Pipe;
 use regex::Regex;
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use crate::r2pipe_cache::R2PipeCache;
+use crate::annotate::{self, FrameRegisters};
+use crate::app_bundle::AppBundle;
+use crate::binary_locator::{BinaryCandidate, BinaryLocator, Lookup};
+use crate::byte_stream::{self, StreamInstruction};
+use crate::cfg;
+use crate::config::{Config, ThreadSelection};
+use crate::decoders::esr::{self, DecodedEsr};
+use crate::decompiler::{self, Decompiler};
+use crate::demangle::{self, Demangler};
+use crate::decoders::{mach_exception, termination};
+use crate::dwarf::{self, SourceLocation, Symbolizer};
+use crate::dyld_cache::{CacheFile, CacheImage, CacheMapping, DyldSharedCache};
+use crate::macho::{MachOImage, MachOSlice};
+use crate::parsers::{self, ParseError, ParsedReport};
+use crate::pointer_auth::PointerAuth;
+use crate::provenance;
+use crate::r2pipe_cache::{self, R2PipeCache};
 use crate::structs::crash_info::*;
 use crate::structs::incident_report::IncidentReport;
+use crate::symbol_store::{self, SymbolDir};
+use crate::symbolicator::{SymbolMatch, SymbolTable};
 
 lazy_static! {
-    pub static ref OBJC_METHOD: Regex = Regex::new(
-        r#"[-|+]\[(.*) (.*)][\._]*(block_invoke[\._]*[0-9]*)*[\._]*(cold[\._]*[0-9]*)*"#
-    )
-    .unwrap();
+    // Address at the start of a line of r2 disassembly, after the flow graph art
+    pub static ref ASM_ADDRESS: Regex = Regex::new(r"^[^0-9A-Za-z;]*0x([0-9A-Fa-f]+)\s").unwrap();
 }
 
 pub const OTA_FOLDER: &str = "dylibs";
 pub const APPS_FOLDER: &str = "apps";
 
-fn resolve_symbol_address(r2: &mut R2Pipe, symbol_name: &String) -> Option<String> {
-    if symbol_name.contains("[") {
-        if let Some(matches) = OBJC_METHOD.captures(symbol_name) {
-            let class_name: &str = matches.get(1).unwrap().as_str();
-            let selector_name: &str = matches.get(2).unwrap().as_str();
-            let block_invoke: Option<regex::Match<'_>>  = matches.get(3);
-            let cold: Option<regex::Match<'_>> = matches.get(4);
+// Symbol table of a Mach-O slice, read once. Tables are keyed by UUID so that
+// a binary found for several images is read once; their names are demangled
+// when they are read.
+fn load_symbol_table<'a>(
+    symbol_tables: &'a mut HashMap<String, Option<SymbolTable>>,
+    demangler: &mut Demangler,
+    path: &Path,
+    slice: &MachOSlice,
+) -> &'a Option<SymbolTable> {
+    let key: String = match &slice.uuid {
+        Some(uuid) => uuid.clone(),
+        None => format!("{}#{}", path.display(), slice.arch),
+    };
 
-            let mut search_command: String = String::from("is");
-            search_command.push_str(format!(" ~{}", class_name).as_str());
-            search_command.push_str(format!("~{}]", selector_name).as_str());
-            
-            match block_invoke {
-                Some(b) => search_command.push_str(format!("~{}", b.as_str()).as_str()),
-                None => search_command.push_str(format!("~!block_invoke").as_str())
-            }
+    symbol_tables.entry(key).or_insert_with(|| {
+        let table: Option<SymbolTable> = SymbolTable::load(path, slice);
+        if let Some(table) = &table {
+            table.prefetch_names(demangler);
+        }
+        table
+    })
+}
 
-            match cold {
-                Some(c) => search_command.push_str(format!("~{}", c.as_str()).as_str()),
-                None => search_command.push_str(format!("~!cold").as_str())
-            }
-            
-            search_command = format!("{}[2]", search_command);
+// Looks the symbol of a frame up by name in the binary found for its image
+fn find_symbol(
+    symbol_tables: &mut HashMap<String, Option<SymbolTable>>,
+    demangler: &mut Demangler,
+    binary: &LocatedBinary,
+    name: &str,
+    image_offset: u64,
+) -> SymbolMatch {
+    let slice: &MachOSlice = match &binary.slice {
+        Some(slice) => slice,
+        None => return SymbolMatch::NotFound,
+    };
+
+    match load_symbol_table(symbol_tables, demangler, Path::new(&binary.path), slice) {
+        Some(table) => table.find(name, demangler, image_offset),
+        None => SymbolMatch::NotFound,
+    }
+}
 
-            /*if cold_num.is_empty() && block_invoke_num.is_empty() {
-                search_command = format!("{}:0", search_command);
-            }*/
+// Formats a register line, followed by the symbol or selector it points to when known.
+// `canonical` is the PAC-stripped value, printed only when it differs from the raw one.
+fn format_register(name: &str, register: &Register, canonical: Option<u64>) -> String {
+    let mut res: String = format!("{name}: {:#x}", register.value);
+    if let Some(canonical) = canonical.filter(|c| *c != register.value) {
+        res.push_str(format!(" -> {:#x}", canonical).as_str());
+    }
 
-            //println!("{}", search_command);
+    if let Some(objc_selector) = &register.objc_selector {
+        res.push_str(format!("{:>50}\n", objc_selector).as_str());
+    } else if let Some(symbol) = &register.symbol {
+        res.push_str(format!("{:>50}\n", symbol).as_str());
+    } else {
+        res.push('\n');
+    }
 
-            let symbol_address = r2.cmd(&search_command).unwrap();
+    res
+}
 
-            Some(symbol_address)
-        } else {
-            println!("No match found!");
+// Slide-free address of `image_offset` in the binary loaded in r2: the
+// offset from the unslid base of the image. None when r2 lists no sections.
+fn unslid_address(r2: &mut R2Pipe, image_offset: u64) -> Option<u64> {
+    let sections: serde_json::Value = r2.cmdj("iSSj").ok()?;
+    let image_base_no_aslr: u64 = sections.get(0)?["vaddr"].as_u64()?;
+
+    Some(image_base_no_aslr + image_offset)
+}
+
+// Instructions shown on each side of the marked instruction when the start of
+// its function is unknown and no window is configured
+const DEFAULT_WINDOW: u32 = 8;
+
+// Address of the instruction a frame points to: the faulting instruction in
+// frame 0, the call site (the instruction before the return address) in the
+// callers
+fn marked_address(r2: &mut R2Pipe, i: usize, address: u64, arch: Option<&str>) -> u64 {
+    if i == 0 {
+        return address;
+    }
+    // Fixed size instructions
+    if arch.is_none_or(|arch| arch.starts_with("arm")) {
+        return address - 4;
+    }
+
+    r2.cmdj(format!("pdj -1 @ {:#x}", address).as_str())
+        .ok()
+        .and_then(|instructions| instructions.get(0)?["offset"].as_u64())
+        .filter(|offset| *offset < address)
+        .unwrap_or(address - 1)
+}
+
+// Start of the function containing an address, from r2's analysis
+fn function_start(r2: &mut R2Pipe, address: u64) -> Option<u64> {
+    let function: serde_json::Value = r2.cmdj(format!("afij @ {:#x}", address).as_str()).ok()?;
+
+    function.get(0)?["offset"]
+        .as_u64()
+        .filter(|start| *start <= address)
+}
 
-            None
+// Disassembles the code of a frame: from the start of its function up to the
+// marked instruction, or `window` instructions on each side of it
+fn disassemble_frame(
+    r2: &mut R2Pipe,
+    function_start: Option<u64>,
+    marked: u64,
+    window: Option<u32>,
+) -> Result<String, r2pipe::Error> {
+    let mut commands: Vec<String> = Vec::new();
+
+    match (window, function_start) {
+        (None, Some(start)) => {
+            if marked > start {
+                commands.push(format!("pD {} @ {:#x}", marked - start, start));
+            }
+            commands.push(format!("pd 1 @ {:#x}", marked));
+        }
+        (window, _) => {
+            let window: u32 = window.unwrap_or(DEFAULT_WINDOW);
+            if window > 0 {
+                commands.push(format!("pd -{} @ {:#x}", window, marked));
+            }
+            commands.push(format!("pd {} @ {:#x}", window + 1, marked));
         }
+    }
+
+    let mut asm: String = String::new();
+    for command in commands {
+        let output: String = r2.cmd(command.as_str())?;
+        asm.push_str(output.trim_end_matches('\n'));
+        asm.push('\n');
+    }
+
+    Ok(asm)
+}
+
+// Label of the marked instruction of a frame
+fn mark_label(i: usize) -> &'static str {
+    if i == 0 {
+        "<== crashing instruction"
     } else {
-        let mut search_command: String = format!("is ~{}", symbol_name);
-        search_command.push_str(format!(
-            "~{}block_invoke",
-            if symbol_name.contains("block_invoke") {
-                ""
+        "<== call site"
+    }
+}
+
+// Flags the marked instruction in a r2 listing
+fn mark_instruction(asm: &str, marked: u64, i: usize) -> String {
+    let label: &str = mark_label(i);
+
+    asm.lines()
+        .map(|line| {
+            let address: Option<u64> = ASM_ADDRESS
+                .captures(line)
+                .and_then(|c| u64::from_str_radix(c.get(1).unwrap().as_str(), 16).ok());
+            if address == Some(marked) {
+                format!("{}  {}\n", line, label)
             } else {
-                "!"
+                format!("{}\n", line)
             }
-        ).as_str());
-        search_command.push_str("[2]:0"); // ~FUNC~GLOBAL
+        })
+        .collect()
+}
 
-        //println!("{}", search_command);
+// Decompiles the function of a frame with the configured decompiler, marking
+// the line of the marked instruction. Errs with a note when the assembly has
+// to be shown instead.
+fn decompile_frame(
+    r2: &mut R2Pipe,
+    choice: &str,
+    function_start: Option<u64>,
+    marked: u64,
+    i: usize,
+) -> Result<String, String> {
+    let decompiler: &Decompiler = decompiler::select(r2, choice)
+        .ok_or_else(|| format!("!!! Decompiler {} not available, showing the assembly\n", choice))?;
 
-        let symbol_address: String = r2.cmd(&search_command).unwrap();
+    decompiler
+        .decompile(r2, function_start, marked, mark_label(i))
+        .ok_or_else(|| {
+            format!(
+                "!!! {} could not decompile the function at {:#x}, showing the assembly\n",
+                decompiler.command, marked
+            )
+        })
+}
 
-        Some(symbol_address)
+// Name of the control flow graph files of a frame. Frames of the threads
+// rendered on request are prefixed with their thread.
+fn cfg_name(thread: Option<usize>, i: usize) -> String {
+    match thread {
+        Some(thread) => format!("thread_{}_frame_{}", thread, i),
+        None => format!("frame_{}", i),
     }
 }
 
+// Writes the control flow graph of a frame in `dir` and returns the report
+// lines referencing its files
+fn export_cfg(dir: &Path, name: &str, dot: Option<&str>) -> String {
+    let dot: &str = match dot {
+        Some(dot) => dot,
+        None => return format!("!!! No function analyzed for the control flow graph {}\n", name),
+    };
+
+    match cfg::write(dot, &dir.join(name)) {
+        Ok(files) => {
+            let mut res: String = format!("{:<10} CFG: {}", "", files.dot.display());
+            if let Some(svg) = files.svg {
+                res.push_str(format!(", {}", svg.display()).as_str());
+            }
+            res.push('\n');
+            res
+        }
+        Err(e) => {
+            println!("Unable to write the control flow graph {}: {}", name, e);
+            format!("!!! Unable to write the control flow graph {}: {}\n", name, e)
+        }
+    }
+}
+
+// Index, id, name and dispatch queue of a thread
+fn format_thread_header(index: usize, thread: &Thread) -> String {
+    let mut res: String = format!(
+        "Thread {}{}\n",
+        index,
+        if thread.triggered.is_some() { " Crashed" } else { "" }
+    );
+    res.push_str(format!("  {:<18} {}\n", "Id:", thread.id).as_str());
+    if let Some(name) = &thread.name {
+        res.push_str(format!("  {:<18} {}\n", "Name:", name).as_str());
+    }
+    if let Some(queue) = &thread.queue {
+        res.push_str(format!("  {:<18} {}\n", "Queue:", queue).as_str());
+    }
+    res.push('\n');
+
+    res
+}
+
+// Human readable decoding of ESR_EL1, checked against FAR
+fn format_fault_analysis(state: &Arm64ThreadState, far: u64) -> String {
+    let mut res: String = String::from("\nFault analysis\n\n");
+    let decoded: DecodedEsr = esr::decode(state.esr.value);
+
+    res.push_str(format!("{:<20} {:#x}\n", "ESR:", state.esr.value).as_str());
+    res.push_str(
+        format!(
+            "{:<20} {:#04x} {}\n",
+            "Exception class:", decoded.exception_class, decoded.class_name
+        )
+        .as_str(),
+    );
+    res.push_str(
+        format!(
+            "{:<20} {}\n",
+            "Instruction length:",
+            if decoded.il { "32-bit" } else { "16-bit" }
+        )
+        .as_str(),
+    );
+    res.push_str(format!("{:<20} {:#08x}\n", "ISS:", decoded.iss).as_str());
+    decoded.details.iter().for_each(|(label, value)| {
+        res.push_str(format!("  {:<18} {}\n", label, value).as_str());
+    });
+    match decoded.far_valid {
+        Some(true) => res.push_str(format!("{:<20} yes ({:#x})\n", "FAR valid:", far).as_str()),
+        Some(false) => res.push_str(format!("{:<20} no\n", "FAR valid:").as_str()),
+        None => {}
+    }
+    if !state.esr.description.is_empty() {
+        res.push_str(format!("{:<20} {}\n", "Report says:", state.esr.description).as_str());
+    }
+
+    res
+}
+
+fn format_x86_registers(state: &X86ThreadState) -> String {
+    let mut res: String = String::new();
+
+    [
+        ("rax", &state.rax),
+        ("rbx", &state.rbx),
+        ("rcx", &state.rcx),
+        ("rdx", &state.rdx),
+        ("rdi", &state.rdi),
+        ("rsi", &state.rsi),
+        ("rbp", &state.rbp),
+        ("rsp", &state.rsp),
+        ("r8", &state.r8),
+        ("r9", &state.r9),
+        ("r10", &state.r10),
+        ("r11", &state.r11),
+        ("r12", &state.r12),
+        ("r13", &state.r13),
+        ("r14", &state.r14),
+        ("r15", &state.r15),
+    ]
+    .iter()
+    .for_each(|(name, r)| res.push_str(format_register(name, r, None).as_str()));
+
+    res.push_str(format!("rip: {:#x}\n", state.rip.value).as_str());
+    res.push_str(format!("rflags: {:#x}\n", state.rflags.value).as_str());
+    res.push_str(format!("cr2: {:#x}\n", state.cr2.value).as_str());
+    res.push_str(
+        format!(
+            "trap: {} {}\n",
+            state.trap.value,
+            state.trap.description.as_deref().unwrap_or_default()
+        )
+        .as_str(),
+    );
+    res.push_str(format!("err: {:#x}\n", state.err.value).as_str());
+    if let Some(cpu) = &state.cpu {
+        res.push_str(format!("cpu: {}\n", cpu.value).as_str());
+    }
+    res.push_str(format!("flavor: {}\n", state.flavor).as_str());
+
+    res
+}
+
+// Function, file, line and inlined call chain of a frame from the dSYM of its
+// image. Caller frames hold return addresses: the call is looked up at the
+// previous byte.
+fn frame_source_info(symbolizer: &Symbolizer, dwarf: &DwarfFile, i: usize, frame: &Frame) -> String {
+    let address: u64 = dwarf.text_vmaddr + frame.image_offset - if i == 0 { 0 } else { 1 };
+
+    let chain: Vec<SourceLocation> = match symbolizer.symbolize(&dwarf.path, &dwarf.arch, &[address]) {
+        Ok(mut chains) if !chains.is_empty() => chains.remove(0),
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            println!("Unable to read {}: {}", dwarf.path.display(), e);
+            return String::new();
+        }
+    };
+
+    let mut res: String = String::new();
+    for (n, location) in chain.iter().enumerate() {
+        let prefix: &str = if n == 0 { "at" } else { "inlined into" };
+        res.push_str(
+            format!(
+                "{:<10} {} {} ({})\n",
+                "",
+                prefix,
+                location.function,
+                location.file_and_line()
+            )
+            .as_str(),
+        );
+    }
+
+    res
+}
+
+// Inserts the source line of each run of instructions, from the dSYM line
+// table, in a r2 disassembly listing
+fn interleave_source(symbolizer: &mut Symbolizer, dwarf: &DwarfFile, asm: &str) -> String {
+    let addresses: Vec<Option<u64>> = asm
+        .lines()
+        .map(|line| {
+            ASM_ADDRESS
+                .captures(line)
+                .and_then(|c| u64::from_str_radix(c.get(1).unwrap().as_str(), 16).ok())
+        })
+        .collect();
+    let lookups: Vec<u64> = addresses.iter().flatten().copied().collect();
+    let locations: Vec<Vec<SourceLocation>> =
+        match symbolizer.symbolize(&dwarf.path, &dwarf.arch, &lookups) {
+            Ok(locations) if locations.len() == lookups.len() => locations,
+            _ => return asm.to_string(),
+        };
+
+    let mut res: String = String::new();
+    let mut locations = locations.into_iter();
+    let mut current: Option<(String, u32)> = None;
+    for (line, address) in asm.lines().zip(addresses) {
+        if address.is_some() {
+            if let Some(location) = locations.next().and_then(|chain| chain.into_iter().next()) {
+                let key: (String, u32) = (location.file.clone(), location.line);
+                if current.as_ref() != Some(&key) {
+                    let source: String = symbolizer
+                        .source_line(&location.file, location.line)
+                        .unwrap_or_default();
+                    res.push_str(format!("        ; {}  {}\n", location.file_and_line(), source).as_str());
+                    current = Some(key);
+                }
+            }
+        }
+        res.push_str(line);
+        res.push('\n');
+    }
+
+    res
+}
+
+// dSYM DWARF file of a used image
+struct DwarfFile {
+    path: PathBuf,
+    arch: String,
+    // vmaddr of __TEXT, which image offsets are relative to
+    text_vmaddr: u64,
+}
+
+// Binary chosen on disk for a used image
+struct LocatedBinary {
+    path: String,
+    // Architecture of the slice to open in fat binaries
+    arch: Option<String>,
+    // Slice read from the file, None when it is not a readable Mach-O
+    slice: Option<MachOSlice>,
+    // Set when the binary can't be trusted to be the one that crashed
+    warning: Option<String>,
+}
+
+// Code of a used image found in a dyld_shared_cache
+struct SharedCacheCode {
+    // Cache file (main cache or subcache) backing the address
+    file_path: String,
+    mappings: Vec<CacheMapping>,
+    arch: String,
+    // Unslid address in the shared region
+    address: u64,
+    warning: Option<String>,
+}
+
 pub struct CrashLogAnalyzer<'a> {
     pub general_info: IncidentReport,
     pub crash_info: CrashInfo,
     pub r2_cache: R2PipeCache,
     pub filtered_dylibs: HashSet<&'a str>,
+    pub pointer_auth: Option<PointerAuth>,
+    pub binary_locator: BinaryLocator,
+    pub search_paths: Vec<PathBuf>,
+    pub symbol_dir: SymbolDir,
+    pub dyld_caches: Vec<DyldSharedCache>,
+    // Kept alive for as long as the analysis runs: unpacked .ipa files live in
+    // temporary folders removed on drop
+    pub app_bundles: Vec<AppBundle>,
+    pub symbolizer: Symbolizer,
+    pub interleave_source: bool,
+    // Instructions shown around the marked instruction of each frame
+    pub window: Option<u32>,
+    // Trace back the register used by the crashing instruction
+    pub provenance: bool,
+    // Decompiler shown instead of the assembly ("auto" or its r2 command)
+    pub decompiler: Option<String>,
+    // Folder the control flow graphs of the frames are written to, when exported
+    pub cfg_dir: Option<PathBuf>,
+    // Threads rendered besides the crashing one, and the ones disassembled
+    pub threads: Option<ThreadSelection>,
+    pub disassemble_threads: Option<ThreadSelection>,
+    // Symbol tables of the binaries found on disk, by image UUID
+    pub symbol_tables: HashMap<String, Option<SymbolTable>>,
+    pub demangler: Demangler,
 }
 
 impl<'a> CrashLogAnalyzer<'a> {
-    pub fn new(ips_data: String) -> CrashLogAnalyzer<'a> {
-        let (gen_info, crash_details): (&str, &str) = ips_data.split_once("}").unwrap();
+    // The binaries of the used images are searched, in order, in the given apps,
+    // in the configured search paths, in the symbol directory of the OS build of the report, in
+    // `apps` and, when no symbol store is configured, in `dylibs`.
+    pub fn new(report_data: String, config: &Config) -> Result<CrashLogAnalyzer<'a>, ParseError> {
+        let report: ParsedReport = parsers::parse(&report_data)?;
+
+        let symbol_dir: SymbolDir =
+            symbol_store::select(&config.symbol_stores, &report.crash_info.os_version);
+
+        let app_bundles: Vec<AppBundle> = config
+            .apps
+            .iter()
+            .filter_map(|path| match AppBundle::open(path) {
+                Ok(bundle) => {
+                    print!("{}", bundle.summary());
+                    Some(bundle)
+                }
+                Err(e) => {
+                    println!("Unable to open {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
 
-        CrashLogAnalyzer {
-            general_info: serde_json::from_str(format!("{}}}", gen_info).as_str())
-                .expect("Failed to parse general info"),
-            crash_info: serde_json::from_str(crash_details).expect("Failed to parse crash details"),
+        let mut search_paths: Vec<PathBuf> =
+            app_bundles.iter().flat_map(|b| b.search_roots()).collect();
+        search_paths.extend(config.search_paths.iter().cloned());
+        if let SymbolDir::Found(dir) = &symbol_dir {
+            search_paths.push(dir.clone());
+        }
+        search_paths.push(PathBuf::from(APPS_FOLDER));
+        if matches!(symbol_dir, SymbolDir::NotConfigured) {
+            search_paths.push(PathBuf::from(OTA_FOLDER));
+        }
+
+        let pointer_auth: Option<PointerAuth> = PointerAuth::for_report(&report.crash_info);
+
+        let mut binary_locator: BinaryLocator = BinaryLocator::new();
+        search_paths
+            .iter()
+            .for_each(|root| binary_locator.index_dir(root));
+
+        let dyld_caches: Vec<DyldSharedCache> = config
+            .dyld_caches
+            .iter()
+            .filter_map(|path| match DyldSharedCache::open(path) {
+                Ok(cache) => Some(cache),
+                Err(e) => {
+                    println!("Unable to open dyld_shared_cache {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
+
+        // Graphs go next to the text report, in a folder named after it
+        let cfg_dir: Option<PathBuf> = config.cfg.then(|| {
+            let report_name: String = Path::new(&config.report_path)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            Path::new(crate::OUTPUT_FOLDER).join(format!("{}_cfg", report_name))
+        });
+
+        Ok(CrashLogAnalyzer {
+            general_info: report.general_info,
+            crash_info: report.crash_info,
             r2_cache: R2PipeCache::new(),
             filtered_dylibs: HashSet::from(["UIKitCore", "libdispatch.dylib", "CoreFoundation", "CFNetwork"]),
-        }
+            pointer_auth,
+            binary_locator,
+            search_paths,
+            symbol_dir,
+            dyld_caches,
+            app_bundles,
+            symbolizer: Symbolizer::new(
+                config
+                    .symbolizer
+                    .as_deref()
+                    .unwrap_or(dwarf::DEFAULT_SYMBOLIZER),
+            ),
+            interleave_source: config.interleave_source,
+            window: config.window,
+            provenance: config.provenance,
+            decompiler: config.decompiler.clone(),
+            cfg_dir,
+            threads: config.threads.clone(),
+            disassemble_threads: config.disassemble_threads.clone(),
+            symbol_tables: HashMap::new(),
+            demangler: Demangler::new(
+                config.keep_mangled,
+                config
+                    .swift_demangler
+                    .as_deref()
+                    .unwrap_or(demangle::DEFAULT_SWIFT_DEMANGLER),
+                config
+                    .cxx_demangler
+                    .as_deref()
+                    .unwrap_or(demangle::DEFAULT_CXX_DEMANGLER),
+            ),
+        })
     }
 
     pub fn parse_general_info(&self) -> String {
@@ -126,6 +624,31 @@ impl<'a> CrashLogAnalyzer<'a> {
             "{:<15} {:<15}\n",
             "OS build:", self.crash_info.os_version.build
         ).as_str());
+        self.app_bundles.iter().for_each(|bundle| {
+            res.push_str(format!("{:<15} {:<15}\n", "App bundle:", bundle.source.display()).as_str())
+        });
+        match &self.symbol_dir {
+            SymbolDir::NotConfigured => {}
+            SymbolDir::UnknownBuild => res.push_str(
+                format!("{:<15} {:<15}\n", "Symbols:", "unknown OS build, no symbol directory selected")
+                    .as_str(),
+            ),
+            SymbolDir::Found(dir) => {
+                res.push_str(format!("{:<15} {:<15}\n", "Symbols:", dir.display()).as_str())
+            }
+            SymbolDir::Missing { build, stores } => {
+                let stores: Vec<String> = stores.iter().map(|s| s.display().to_string()).collect();
+                res.push_str(
+                    format!(
+                        "{:<15} !!! MISSING: no directory for OS build {} in {}, system images will not be disassembled\n",
+                        "Symbols:",
+                        build,
+                        stores.join(", ")
+                    )
+                    .as_str(),
+                );
+            }
+        }
         res.push_str(format!("{:<15} {:<15}\n", "OS model:", self.crash_info.model_code).as_str());
         res.push_str(format!("{:<15} {:<15}\n", "CPU:", self.crash_info.cpu_type).as_str());
         res.push_str(format!("{:<15} {:<15}\n", "User ID:", self.crash_info.user_id).as_str());
@@ -180,21 +703,47 @@ impl<'a> CrashLogAnalyzer<'a> {
             )
             .as_str(),
         );
-        if self.crash_info.termination.is_some() {
+        mach_exception::decode(
+            &self.crash_info.exception,
+            self.crash_info.cpu_type.starts_with("X86"),
+        )
+        .iter()
+        .for_each(|(label, value)| {
+            res.push_str(format!("  {:<18} {}\n", label, value).as_str());
+        });
+        if let Some(termination) = &self.crash_info.termination {
             res.push_str(
                 format!(
                     "{:<20} {:<20}\n",
                     "Termination:",
-                    self.crash_info
-                        .termination
-                        .as_ref()
-                        .unwrap()
+                    termination
                         .indicator
                         .as_ref()
                         .unwrap_or(&String::from("None"))
                 )
                 .as_str(),
             );
+            res.push_str(format!("  {:<18} {}\n", "Namespace:", termination.namespace).as_str());
+            res.push_str(format!("  {:<18} {:#x}\n", "Code:", termination.code).as_str());
+            if let Some(explanation) = termination::explain(termination) {
+                res.push_str(format!("  {:<18} {}\n", "Meaning:", explanation).as_str());
+            }
+            if termination.flags != 0 {
+                res.push_str(format!("  {:<18} {:#x}\n", "Flags:", termination.flags).as_str());
+            }
+            if termination.by_proc.is_some() || termination.by_pid.is_some() {
+                res.push_str(
+                    format!(
+                        "  {:<18} {} [{}]\n",
+                        "Killed by:",
+                        termination.by_proc.as_deref().unwrap_or("???"),
+                        termination
+                            .by_pid
+                            .map_or(String::from("?"), |pid| pid.to_string())
+                    )
+                    .as_str(),
+                );
+            }
         }
 
         res.push_str(format!("{:-<20}\n\n", "").as_str());
@@ -202,254 +751,1034 @@ impl<'a> CrashLogAnalyzer<'a> {
         res
     }
 
-    fn get_frame_info(&mut self, i: usize, frame: &Frame) -> String {
-        let mut res: String = String::new();
+    // Returns the expected location of the binary of a used image, relative to
+    // a search root
+    fn layout_path(&self, image: &UsedImage) -> String {
+        let image_name: &String = image.name.as_ref().unwrap();
 
-        let image: &UsedImage = self
-            .crash_info
+        // Check if is main exe/main app
+        if image_name == &self.crash_info.proc_name {
+            if self.general_info.is_first_party == 1 {
+                self.crash_info.proc_name.clone()
+            } else {
+                format!(
+                    "{}.app/{}",
+                    self.crash_info.proc_name, self.crash_info.proc_name
+                )
+            }
+        } else if image
+            .path
+            .as_ref()
+            .is_some_and(|path| path.contains(&self.crash_info.proc_name))
+        {
+            format!(
+                "{}.app/Frameworks/{}.framework/{}",
+                self.crash_info.proc_name, image_name, image_name
+            )
+        } else {
+            image_name.clone()
+        }
+    }
+
+    // Returns the first search root containing the binary of a used image at
+    // its layout path
+    fn image_path(&self, image: &UsedImage) -> Option<String> {
+        let layout_path: String = self.layout_path(image);
+
+        self.search_paths
+            .iter()
+            .map(|root| root.join(&layout_path))
+            .find(|path| path.exists())
+            .map(|path| path.display().to_string())
+    }
+
+    // Finds the binary of a used image on disk, checking it by UUID.
+    // Returns the slice to open and, when the binary can't be trusted to be
+    // the one that crashed, a warning to put in the report.
+    fn locate_binary(&self, image: &UsedImage) -> Option<LocatedBinary> {
+        let image_name: &str = image.name.as_deref().unwrap_or("???");
+
+        match self.binary_locator.lookup(image) {
+            Lookup::Found(candidate) => {
+                println!(
+                    "BINARY: {} ({} slice at {:#x}, {} bytes)",
+                    candidate.path.display(),
+                    candidate.slice.arch,
+                    candidate.slice.offset,
+                    candidate.slice.size
+                );
+                if let Some(warning) = self.shared_r2_arch_warning(candidate) {
+                    print!("{warning}");
+                }
+
+                Some(LocatedBinary {
+                    path: candidate.path.display().to_string(),
+                    arch: Some(candidate.slice.arch.clone()),
+                    slice: Some(candidate.slice.clone()),
+                    warning: None,
+                })
+            }
+            Lookup::Mismatch(candidate) => {
+                let mut warning: String = format!(
+                    "!!! WARNING: UUID MISMATCH for {}: crashed image is {} ({}), {} is {} ({}).\n\
+                     !!! The disassembly below may not be the code that crashed.\n\n",
+                    image_name,
+                    image.uuid,
+                    image.arch.as_deref().unwrap_or("?"),
+                    candidate.path.display(),
+                    candidate.uuid(),
+                    candidate.slice.arch
+                );
+                if let Some(shared) = self.shared_r2_arch_warning(candidate) {
+                    warning.push_str(shared.as_str());
+                }
+                print!("{warning}");
+
+                Some(LocatedBinary {
+                    path: candidate.path.display().to_string(),
+                    arch: Some(candidate.slice.arch.clone()),
+                    slice: Some(candidate.slice.clone()),
+                    warning: Some(warning),
+                })
+            }
+            Lookup::NotFound => {
+                // Not indexed: the file at the layout path is not a readable Mach-O
+                let image_path: String = self.image_path(image)?;
+
+                let warning: String = format!(
+                    "!!! WARNING: could not read the UUID of {}, it can't be checked against {} ({}).\n\n",
+                    image_path, image_name, image.uuid
+                );
+                print!("{warning}");
+
+                Some(LocatedBinary {
+                    path: image_path,
+                    arch: image.arch.clone(),
+                    slice: None,
+                    warning: Some(warning),
+                })
+            }
+        }
+    }
+
+    // Warns when the file of a candidate holds other slices that r2 opens with
+    // the same arch and bits (arm64 and arm64e): the candidate slice is the
+    // one disassembled, on its own, whatever r2 would pick in the fat file
+    fn shared_r2_arch_warning(&self, candidate: &BinaryCandidate) -> Option<String> {
+        let others: Vec<String> = self
+            .binary_locator
+            .other_slices(candidate)
+            .iter()
+            .filter(|other| r2pipe_cache::same_r2_arch(&other.slice.arch, &candidate.slice.arch))
+            .map(|other| format!("{} at {:#x} ({})", other.slice.arch, other.slice.offset, other.uuid()))
+            .collect();
+        if others.is_empty() {
+            return None;
+        }
+
+        Some(format!(
+            "!!! WARNING: {} also holds {}, which r2 can't tell from the {} slice by arch.\n\
+             !!! Only the {} slice at {:#x} ({}) is disassembled.\n\n",
+            candidate.path.display(),
+            others.join(", "),
+            candidate.slice.arch,
+            candidate.slice.arch,
+            candidate.slice.offset,
+            candidate.uuid()
+        ))
+    }
+
+    // Finds the code of a used image in the configured dyld_shared_caches.
+    // The cache is chosen by the report's sharedCache UUID, or by the image UUID
+    // when none matches, and the address is unslid with the report's sharedCache
+    // base when the cache is the one of the report.
+    fn locate_in_shared_cache(&self, image: &UsedImage, image_offset: u64) -> Option<SharedCacheCode> {
+        let report_cache: Option<&SharedCache> = self.crash_info.shared_cache.as_ref();
+        let is_report_cache = |cache: &DyldSharedCache| {
+            report_cache.is_some_and(|shared| shared.uuid.eq_ignore_ascii_case(&cache.uuid))
+        };
+        let in_report_cache: bool = report_cache
+            .is_some_and(|shared| image.base >= shared.base && image.base < shared.base + shared.size);
+
+        let cache: &DyldSharedCache = self
+            .dyld_caches
+            .iter()
+            .find(|cache| is_report_cache(cache) && in_report_cache)
+            .or_else(|| self.dyld_caches.iter().find(|cache| cache.image(&image.uuid).is_some()))?;
+        let cache_image: Option<&CacheImage> = cache.image(&image.uuid);
+
+        let mut warning: Option<String> = None;
+        let address: u64 = match (report_cache, cache_image) {
+            (Some(shared), _) if is_report_cache(cache) && in_report_cache => {
+                image.base + image_offset - (shared.base - cache.base_address)
+            }
+            (_, Some(cache_image)) => {
+                if let Some(shared) = report_cache {
+                    warning = Some(format!(
+                        "!!! WARNING: the report's shared cache is {}, {} is {}. {} was matched by UUID.\n\n",
+                        shared.uuid,
+                        cache.path.display(),
+                        cache.uuid,
+                        image.name.as_deref().unwrap_or("???")
+                    ));
+                }
+                cache_image.load_address + image_offset
+            }
+            _ => return None,
+        };
+
+        if let Some(cache_image) = cache_image {
+            if image_offset >= cache_image.text_size as u64 {
+                warning = Some(format!(
+                    "!!! WARNING: offset {:#x} is outside the __TEXT of {} in the shared cache.\n\n",
+                    image_offset, cache_image.path
+                ));
+            }
+        }
+
+        let file: &CacheFile = cache.file_for_address(address)?;
+        println!(
+            "SHARED CACHE: {} ({} at {:#x} in {})",
+            cache.path.display(),
+            cache_image.map_or("?", |c| c.path.as_str()),
+            address,
+            file.path.display()
+        );
+
+        Some(SharedCacheCode {
+            file_path: file.path.display().to_string(),
+            mappings: file.mappings.clone(),
+            arch: cache.arch.clone(),
+            address,
+            warning,
+        })
+    }
+
+    // Disassembles a frame of an image that lives in a dyld_shared_cache. The
+    // cache is not analyzed, so the function is delimited by the symbol offset
+    // of the frame when the report has one.
+    fn disassemble_shared_cache_frame(
+        &mut self,
+        i: usize,
+        frame: &Frame,
+        code: &SharedCacheCode,
+        registers: Option<FrameRegisters>,
+        thread: Option<usize>,
+    ) -> Result<String, r2pipe::Error> {
+        let r2: &mut R2Pipe = self.r2_cache.get_or_create_mapped(
+            code.file_path.as_str(),
+            Some(code.arch.as_str()),
+            &code.mappings,
+        )?;
+
+        let marked: u64 = marked_address(r2, i, code.address, Some(code.arch.as_str()));
+        let start: Option<u64> = match (frame.symbol.as_ref(), frame.symbol_location) {
+            (Some(_), Some(symbol_location)) => Some(code.address - symbol_location as u64),
+            _ => function_start(r2, marked),
+        };
+        let decompiled: Option<Result<String, String>> = self
+            .decompiler
+            .as_deref()
+            .map(|choice| decompile_frame(r2, choice, start, marked, i));
+        let mut asm: String = match decompiled {
+            Some(Ok(code)) => code,
+            fallback => {
+                let mut asm: String = disassemble_frame(r2, start, marked, self.window)?;
+                if let Some(registers) = registers {
+                    asm = annotate::annotate_arm64(&asm, registers, self.pointer_auth, marked);
+                }
+                let mut res: String = match fallback {
+                    Some(Err(note)) => {
+                        println!("{}", note.trim_start_matches("!!! ").trim_end());
+                        note
+                    }
+                    _ => String::new(),
+                };
+                res.push_str(mark_instruction(&asm, marked, i).as_str());
+                res
+            }
+        };
+        if self.provenance && matches!(registers, Some(FrameRegisters::Crash(_))) {
+            asm.push('\n');
+            asm.push_str(provenance::trace(r2, marked, start).as_str());
+        }
+        let graph: Option<String> = self
+            .cfg_dir
+            .is_some()
+            .then(|| cfg::dot(r2, start, marked, mark_label(i)))
+            .flatten();
+        if let Some(dir) = &self.cfg_dir {
+            let graph: Option<String> = graph.map(|graph| self.demangler.demangle_text(&graph));
+            asm.push_str(export_cfg(dir, &cfg_name(thread, i), graph.as_deref()).as_str());
+        }
+
+        Ok(asm)
+    }
+
+    // The dSYM DWARF file of a used image
+    fn dwarf_file(&self, image: &UsedImage) -> Option<DwarfFile> {
+        let dsym = self.binary_locator.dsym(image)?;
+
+        Some(DwarfFile {
+            path: dsym.path.clone(),
+            arch: dsym.slice.arch.clone(),
+            text_vmaddr: dsym.slice.text_vmaddr?,
+        })
+    }
+
+    // Symbol table of a used image, read from its binary when one with the same
+    // UUID is on disk
+    fn symbol_table(&mut self, image_index: usize) -> Option<&SymbolTable> {
+        let image: &UsedImage = self.crash_info.used_images.get(image_index)?;
+        let candidate = match self.binary_locator.lookup(image) {
+            Lookup::Found(candidate) => candidate.clone(),
+            _ => return None,
+        };
+
+        load_symbol_table(
+            &mut self.symbol_tables,
+            &mut self.demangler,
+            &candidate.path,
+            &candidate.slice,
+        )
+        .as_ref()
+    }
+
+    // Fills the symbol of the frames and registers the report left
+    // unsymbolicated, from the symbol table, function starts and ObjC metadata
+    // of the binaries found on disk. Runs before anything is rendered.
+    pub fn symbolicate(&mut self) {
+        let mut threads: Vec<Thread> = std::mem::take(&mut self.crash_info.threads);
+        for thread in threads.iter_mut() {
+            thread
+                .frames
+                .iter_mut()
+                .for_each(|frame| self.symbolicate_frame(frame));
+            if let Some(state) = thread.thread_state.as_mut() {
+                self.symbolicate_registers(state);
+            }
+        }
+        self.crash_info.threads = threads;
+
+        let mut backtrace: Option<Vec<Frame>> = self.crash_info.last_exception_backtrace.take();
+        if let Some(frames) = backtrace.as_mut() {
+            frames
+                .iter_mut()
+                .for_each(|frame| self.symbolicate_frame(frame));
+        }
+        self.crash_info.last_exception_backtrace = backtrace;
+
+        self.demangle_symbols();
+    }
+
+    // Demangles the symbols of all frames and registers in one pass
+    fn demangle_symbols(&mut self) {
+        let mut symbols: Vec<&mut Option<String>> = Vec::new();
+        for thread in self.crash_info.threads.iter_mut() {
+            symbols.extend(thread.frames.iter_mut().map(|frame| &mut frame.symbol));
+            match thread.thread_state.as_mut() {
+                Some(ThreadState::Arm64(state)) => {
+                    let state: &mut Arm64ThreadState = state;
+                    symbols.extend(state.x.iter_mut().map(|r| &mut r.symbol));
+                    symbols.extend([
+                        &mut state.fp.symbol,
+                        &mut state.lr.symbol,
+                        &mut state.sp.symbol,
+                        &mut state.pc.symbol,
+                        &mut state.far.symbol,
+                    ]);
+                }
+                Some(ThreadState::X86_64(state)) => {
+                    let state: &mut X86ThreadState = state;
+                    symbols.extend([
+                        &mut state.rax.symbol,
+                        &mut state.rbx.symbol,
+                        &mut state.rcx.symbol,
+                        &mut state.rdx.symbol,
+                        &mut state.rdi.symbol,
+                        &mut state.rsi.symbol,
+                        &mut state.rbp.symbol,
+                        &mut state.rsp.symbol,
+                        &mut state.r8.symbol,
+                        &mut state.r9.symbol,
+                        &mut state.r10.symbol,
+                        &mut state.r11.symbol,
+                        &mut state.r12.symbol,
+                        &mut state.r13.symbol,
+                        &mut state.r14.symbol,
+                        &mut state.r15.symbol,
+                        &mut state.rip.symbol,
+                    ]);
+                }
+                None => {}
+            }
+        }
+        if let Some(frames) = self.crash_info.last_exception_backtrace.as_mut() {
+            symbols.extend(frames.iter_mut().map(|frame| &mut frame.symbol));
+        }
+
+        let names: Vec<String> = symbols.iter().filter_map(|s| (**s).clone()).collect();
+        let names: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+        self.demangler.prefetch(&names);
+
+        for symbol in symbols {
+            if let Some(name) = symbol.as_mut() {
+                *name = self.demangler.demangle(name);
+            }
+        }
+    }
+
+    fn symbolicate_frame(&mut self, frame: &mut Frame) {
+        if frame.symbol.is_some() {
+            return;
+        }
+
+        if let Some((name, offset)) = self
+            .symbol_table(frame.image_index as usize)
+            .and_then(|table| table.lookup(frame.image_offset))
+        {
+            frame.symbol = Some(name.to_string());
+            frame.symbol_location = Some(offset as u32);
+        }
+    }
+
+    fn symbolicate_registers(&mut self, state: &mut ThreadState) {
+        let registers: Vec<&mut Register> = match state {
+            ThreadState::Arm64(state) => {
+                let state: &mut Arm64ThreadState = state;
+                state
+                    .x
+                    .iter_mut()
+                    .chain([
+                        &mut state.fp,
+                        &mut state.lr,
+                        &mut state.sp,
+                        &mut state.pc,
+                        &mut state.far,
+                    ])
+                    .collect()
+            }
+            ThreadState::X86_64(state) => {
+                let state: &mut X86ThreadState = state;
+                vec![
+                    &mut state.rax,
+                    &mut state.rbx,
+                    &mut state.rcx,
+                    &mut state.rdx,
+                    &mut state.rdi,
+                    &mut state.rsi,
+                    &mut state.rbp,
+                    &mut state.rsp,
+                    &mut state.r8,
+                    &mut state.r9,
+                    &mut state.r10,
+                    &mut state.r11,
+                    &mut state.r12,
+                    &mut state.r13,
+                    &mut state.r14,
+                    &mut state.r15,
+                    &mut state.rip,
+                ]
+            }
+        };
+
+        for register in registers {
+            if register.symbol.is_some() || register.objc_selector.is_some() {
+                continue;
+            }
+
+            let value: u64 = self.pointer_auth.map_or(register.value, |p| p.strip(register.value));
+            let image_index: usize = match self.image_for_address(value) {
+                Some(image_index) => image_index,
+                None => continue,
+            };
+            let image_offset: u64 = value - self.crash_info.used_images[image_index].base;
+            if let Some((name, offset)) = self
+                .symbol_table(image_index)
+                .and_then(|table| table.lookup(image_offset))
+            {
+                register.symbol = Some(name.to_string());
+                register.symbol_location = Some(offset);
+            }
+        }
+    }
+
+    // Index of the used image whose address range contains `address`
+    fn image_for_address(&self, address: u64) -> Option<usize> {
+        self.crash_info
             .used_images
-            .get(frame.image_index as usize)
-            .unwrap();
-        let image_name: Option<&String> = image.name.as_ref();
+            .iter()
+            .position(|image| address >= image.base && address < image.base + image.size)
+    }
+
+    // Resolves a code address to "image + offset" and, when the binary is
+    // available, to the symbol containing it, like a stack frame
+    fn resolve_code_address(&mut self, address: u64) -> Option<String> {
+        let image_index: usize = self.image_for_address(address)?;
+        let image: &UsedImage = &self.crash_info.used_images[image_index];
+        let image_name: &String = image.name.as_ref()?;
+        let image_offset: u64 = address - image.base;
+
+        let mut res: String = format!("{} + {:#x}", image_name, image_offset);
+
+        if self.filtered_dylibs.contains(image_name.as_str()) {
+            return Some(res);
+        }
+        let binary: LocatedBinary = match self.locate_binary(image) {
+            Some(binary) => binary,
+            None => return Some(res),
+        };
+
+        let r2: &mut R2Pipe = match self
+            .r2_cache
+            .get_or_create(binary.path.as_str(), binary.arch.as_deref(), binary.slice.as_ref())
+        {
+            Ok(r2) => r2,
+            Err(e) => {
+                println!("Unable to open {} with r2: {}", binary.path, e);
+                return Some(res);
+            }
+        };
+        // Without sections or a symbol, the address stays image + offset
+        let symbol: String = match unslid_address(r2, image_offset)
+            .and_then(|to_address| r2.cmd(format!("fd @ {:#x}", to_address).as_str()).ok())
+        {
+            Some(symbol) => symbol,
+            None => return Some(res),
+        };
+        if !symbol.trim().is_empty() {
+            let symbol: String = self.demangler.demangle_text(symbol.trim());
+            res.push_str(format!(" ({})", symbol).as_str());
+        }
+
+        Some(res)
+    }
+
+    // `registers` are the registers known at the marked instruction: the state
+    // of the crashing thread for its frame 0, the ones rebuilt from the frame
+    // chain for the callers. `thread` is None for the frames disassembled by default (the crashing
+    // thread or the last exception backtrace), the index of the thread for the
+    // other threads rendered on request: they are only disassembled when
+    // chosen with --disassemble-threads.
+    fn get_frame_info(
+        &mut self,
+        i: usize,
+        frame: &Frame,
+        registers: Option<FrameRegisters>,
+        thread: Option<usize>,
+    ) -> String {
+        let mut res: String = String::new();
+
+        let image: Option<&UsedImage> = self.crash_info.used_images.get(frame.image_index as usize);
+        let image_name: Option<&String> = image.and_then(|image| image.name.as_ref());
         if image_name.is_none() {
             println!("Image has no name");
 
             res.push_str(
                 format!("{:<10} {:<25} 0x{:<25X}\n", i, "???", frame.image_offset).as_str(),
             );
-            res.push_str("\n");
+            res.push('\n');
 
             return res;
         }
+        let image: &UsedImage = image.unwrap();
 
         let symbol_name = match frame.symbol.as_ref() {
             Some(name) => name,
             None => &format!("{:#0x} + {:#0x}", image.base, frame.image_offset),
         };
+        let symbol_display: String = match (frame.symbol.as_ref(), frame.symbol_location) {
+            (Some(symbol), Some(location)) => format!("{} + {}", symbol, location),
+            _ => symbol_name.clone(),
+        };
         res.push_str(
             format!(
                 "{:<10} {:<25} {:<25}\n",
                 i,
                 image.name.as_ref().unwrap(),
-                symbol_name
+                symbol_display
             )
             .as_str(),
         );
-        res.push_str("\n");
+        let dwarf: Option<DwarfFile> = self.dwarf_file(image);
+        if let Some(dwarf) = &dwarf {
+            // llvm-symbolizer prints linkage names, and can't demangle Swift ones
+            let source_info: String = frame_source_info(&self.symbolizer, dwarf, i, frame);
+            res.push_str(self.demangler.demangle_text(&source_info).as_str());
+        }
+        res.push('\n');
 
-        let image_path: String;
-        // Check if is main exe/main app
         println!("IMAGE: {}", image_name.unwrap());
-        if image_name.unwrap() == &self.crash_info.proc_name {
-            if self.general_info.is_first_party == 1 {
-                image_path = format!(
-                    "{APPS_FOLDER}/{}",
-                    self.crash_info.proc_name
-                );
-            }
-            else{
-                image_path = format!(
-                    "{APPS_FOLDER}/{}.app/{}",
-                    self.crash_info.proc_name, self.crash_info.proc_name
-                );
-            }
-        } else if image.path
-            .as_ref()
-            .unwrap()
-            .contains(&self.crash_info.proc_name){
-            image_path = format!(
-                "{APPS_FOLDER}/{}.app/Frameworks/{}.framework/{}",
-                self.crash_info.proc_name,
-                image_name.unwrap(),
-                image_name.unwrap()
-            );
-        }        
-        else {
-            image_path = format!("{}/{}", OTA_FOLDER, image_name.unwrap());
-        }
 
-        if self.filtered_dylibs.contains(image_name.unwrap().as_str()) {
+        let disassemble: bool = thread.is_none_or(|thread| {
+            self.disassemble_threads
+                .as_ref()
+                .is_some_and(|selection| selection.contains(thread))
+        });
+        if !disassemble || self.filtered_dylibs.contains(image_name.unwrap().as_str()) {
             return res;
         }
 
-        if !Path::new(&image_path).exists() {
-            println!("{image_path} not found.");
-            return res;
-        }
+        let binary: LocatedBinary = match self.locate_binary(image) {
+            Some(binary) => {
+                if let Some(warning) = &binary.warning {
+                    res.push_str(warning.as_str());
+                }
+                binary
+            }
+            None => {
+                if let Some(code) = self.locate_in_shared_cache(image, frame.image_offset) {
+                    if let Some(warning) = &code.warning {
+                        res.push_str(warning.as_str());
+                    }
+                    match self.disassemble_shared_cache_frame(i, frame, &code, registers, thread) {
+                        Ok(asm) => res.push_str(self.demangler.demangle_text(&asm).as_str()),
+                        Err(e) => {
+                            println!("Unable to open {} with r2: {}", code.file_path, e);
+                            res.push_str(
+                                format!("!!! Unable to open {} with r2: {}\n", code.file_path, e).as_str(),
+                            );
+                        }
+                    }
+                    res.push('\n');
+
+                    return res;
+                }
+
+                println!("{} not found in search paths.", self.layout_path(image));
+                let is_system_image: bool = image
+                    .path
+                    .as_ref()
+                    .is_some_and(|path| path.starts_with("/System/") || path.starts_with("/usr/"));
+                if let (SymbolDir::Missing { build, .. }, true) = (&self.symbol_dir, is_system_image) {
+                    res.push_str(format!("!!! No symbols for OS build {}\n\n", build).as_str());
+                }
+                return res;
+            }
+        };
 
         println!("SYMBOL: {}", symbol_name);
 
-        let r2: &mut R2Pipe = self.r2_cache.get_or_create(image_path.as_str());
-        if symbol_name.contains(" + ") {
-            let image_base_no_aslr = r2.cmdj("iSSj").unwrap().get(0).unwrap()["vaddr"]
-                .as_u64()
-                .unwrap();
-            //println!("REAL BASE: {:#0x}", image_base_no_aslr);
-            let aslr_slide: u64 = image.base - image_base_no_aslr;
-            let to_address: u64 = (image.base - aslr_slide) + frame.image_offset;
-            let asm = r2
-                .cmd(format!("s {:#0x}; sf.; pdua {:#0x}", to_address, to_address).as_str())
-                .unwrap();
-            res.push_str(format!("{asm}").as_str());
-            res.push_str("\n");
+        let symbol_match: SymbolMatch = if symbol_name.contains(" + ") {
+            SymbolMatch::NotFound
         } else {
-            let symbol_address = resolve_symbol_address(r2, symbol_name);
-            match symbol_address {
-                Some(address) => {
-                    //println!("{}", address.trim());
-
-                    if frame.symbol_location.is_some() {
-                        let mut bytes_count = frame.symbol_location.unwrap();
-                        if i == 0 {
-                            bytes_count += 4;
-                        }
+            find_symbol(
+                &mut self.symbol_tables,
+                &mut self.demangler,
+                &binary,
+                symbol_name,
+                frame.image_offset,
+            )
+        };
 
-                        r2.cmd(format!("s {}", address).as_str()).unwrap();
-                        let asm = r2.cmd(format!("pD {}", bytes_count).as_str()).unwrap();
-                        res.push_str(format!("{asm}").as_str());
-                    }
+        let r2: &mut R2Pipe = match self
+            .r2_cache
+            .get_or_create(binary.path.as_str(), binary.arch.as_deref(), binary.slice.as_ref())
+        {
+            Ok(r2) => r2,
+            Err(e) => {
+                println!("Unable to open {} with r2: {}", binary.path, e);
+                res.push_str(format!("!!! Unable to open {} with r2: {}\n\n", binary.path, e).as_str());
+                return res;
+            }
+        };
+        let asm_start: usize = res.len();
+        let address: u64 = match unslid_address(r2, frame.image_offset) {
+            Some(address) => address,
+            None => {
+                println!("No sections in {} according to r2", binary.path);
+                res.push_str(format!("!!! No sections in {} according to r2\n\n", binary.path).as_str());
+                return res;
+            }
+        };
+        let marked: u64 = marked_address(r2, i, address, binary.arch.as_deref());
+        let start: Option<u64> = match &symbol_match {
+            SymbolMatch::Found(start) => Some(*start),
+            SymbolMatch::NotFound | SymbolMatch::Ambiguous(_) => {
+                if !symbol_name.contains(" + ") {
+                    let note: String = match &symbol_match {
+                        SymbolMatch::Ambiguous(addresses) => format!(
+                            "!!! {} matches several symbols ({}), disassembling around the frame address\n",
+                            symbol_name,
+                            addresses
+                                .iter()
+                                .map(|a| format!("{:#x}", a))
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        ),
+                        _ => format!(
+                            "!!! {} not found in {}, disassembling around the frame address\n",
+                            symbol_name, binary.path
+                        ),
+                    };
+                    println!("{}", note.trim_start_matches("!!! ").trim_end());
+                    res.push_str(note.as_str());
                 }
-                None => {
-                    let image_base_no_aslr = r2.cmdj("iSSj").unwrap().get(0).unwrap()["vaddr"]
-                        .as_u64()
-                        .unwrap();
-
-                    let aslr_slide = image.base - image_base_no_aslr;
-                    let to_address = (image.base - aslr_slide) + frame.image_offset;
-                    let asm = r2
-                        .cmd(format!("s {:#0x}; sf.; pdua {:#0x}", to_address, to_address).as_str())
-                        .unwrap();
-                    res.push_str(format!("{asm}").as_str());
-                    res.push_str("\n");
+
+                function_start(r2, marked)
+            }
+        };
+        let decompiled: Option<Result<String, String>> = self
+            .decompiler
+            .as_deref()
+            .map(|choice| decompile_frame(r2, choice, start, marked, i));
+        let is_decompiled: bool = matches!(decompiled, Some(Ok(_)));
+        match decompiled {
+            Some(Ok(code)) => res.push_str(code.as_str()),
+            fallback => {
+                if let Some(Err(note)) = fallback {
+                    println!("{}", note.trim_start_matches("!!! ").trim_end());
+                    res.push_str(note.as_str());
+                }
+                match disassemble_frame(r2, start, marked, self.window) {
+                    Ok(mut asm) => {
+                        if let Some(registers) = registers {
+                            asm = annotate::annotate_arm64(&asm, registers, self.pointer_auth, marked);
+                        }
+                        res.push_str(mark_instruction(&asm, marked, i).as_str());
+                    }
+                    Err(e) => {
+                        println!("Unable to disassemble {} with r2: {}", binary.path, e);
+                        res.push_str(
+                            format!("!!! Unable to disassemble {} with r2: {}\n", binary.path, e).as_str(),
+                        );
+                    }
                 }
             }
         }
+        let provenance: Option<String> = (self.provenance && matches!(registers, Some(FrameRegisters::Crash(_))))
+            .then(|| provenance::trace(r2, marked, start));
+        let graph: Option<String> = self
+            .cfg_dir
+            .is_some()
+            .then(|| cfg::dot(r2, start, marked, mark_label(i)))
+            .flatten();
+
+        let asm: String = self.demangler.demangle_text(&res.split_off(asm_start));
+        // Source lines are interleaved by instruction address
+        match (&dwarf, self.interleave_source && !is_decompiled) {
+            (Some(dwarf), true) => {
+                res.push_str(interleave_source(&mut self.symbolizer, dwarf, &asm).as_str())
+            }
+            _ => res.push_str(asm.as_str()),
+        }
+        if let Some(provenance) = provenance {
+            res.push('\n');
+            res.push_str(self.demangler.demangle_text(&provenance).as_str());
+        }
+        if let Some(dir) = &self.cfg_dir {
+            let graph: Option<String> = graph.map(|graph| self.demangler.demangle_text(&graph));
+            res.push_str(export_cfg(dir, &cfg_name(thread, i), graph.as_deref()).as_str());
+        }
 
         //res.push_str(format!("{:>70}\n", "-".repeat(60)).as_str());
-        res.push_str("\n");
+        res.push('\n');
 
         res
     }
 
+    // Registers of an arm64 caller frame known from the frame chain: lr, set by
+    // its call to the return address the frame points to
+    fn caller_registers(&self, frame: &Frame) -> Option<FrameRegisters<'static>> {
+        let image: &UsedImage = self.crash_info.used_images.get(frame.image_index as usize)?;
+        if !image.arch.as_deref().is_some_and(|arch| arch.starts_with("arm64")) {
+            return None;
+        }
+
+        Some(FrameRegisters::Caller {
+            lr: image.base + frame.image_offset,
+        })
+    }
+
+    // Renders the crashing thread, or the last exception backtrace when the
+    // report has one, then the other threads chosen with --threads or
+    // --disassemble-threads
     pub fn analyze_faulting_thread(&mut self) -> String {
         let mut res: String = String::from("STACK TRACE\n\n");
 
-        if self.crash_info.last_exception_backtrace.is_some() {
-            let backtrace: Option<Vec<Frame>> = self.crash_info.last_exception_backtrace.clone();
+        let backtrace: Option<Vec<Frame>> = self.crash_info.last_exception_backtrace.clone();
+        if let Some(backtrace) = &backtrace {
+            backtrace.iter().enumerate().rev().for_each(|(i, frame)| {
+                let registers: Option<FrameRegisters> = match i {
+                    0 => None,
+                    _ => self.caller_registers(frame),
+                };
+                res.push_str(self.get_frame_info(i, frame, registers, None).as_str());
+            });
+        }
 
-            backtrace
-                .as_ref()
-                .unwrap()
+        let threads: Vec<Thread> = self.crash_info.threads.clone();
+        threads.iter().enumerate().for_each(|(t, thread)| {
+            // The crashing thread is disassembled by default, unless the last
+            // exception backtrace already was
+            let is_default: bool = thread.triggered.is_some() && backtrace.is_none();
+            let is_chosen: bool = [&self.threads, &self.disassemble_threads]
+                .iter()
+                .any(|selection| selection.as_ref().is_some_and(|s| s.contains(t)));
+            if !is_default && !is_chosen {
+                return;
+            }
+
+            res.push_str(format_thread_header(t, thread).as_str());
+            thread
+                .frames
                 .iter()
                 .enumerate()
                 .rev()
                 .for_each(|(i, frame)| {
-                    res.push_str(self.get_frame_info(i, frame).as_str());
+                    // The thread state is the one of frame 0
+                    let registers: Option<FrameRegisters> =
+                        match (i, thread.triggered.is_some(), thread.thread_state.as_ref()) {
+                            (0, true, Some(ThreadState::Arm64(state))) => Some(FrameRegisters::Crash(state)),
+                            (0, _, _) => None,
+                            _ => self.caller_registers(frame),
+                        };
+                    let index: Option<usize> = (!is_default).then_some(t);
+                    res.push_str(self.get_frame_info(i, frame, registers, index).as_str());
                 });
+        });
+
+        res
+    }
+
+    // Disassembles the bytes the report captured around the pc of the
+    // crashing thread (instructionByteStream) and compares them with the ones
+    // at the same offset in the binary on disk. Different bytes mean another
+    // build of the binary, code patched at runtime (hooks) or JIT code.
+    pub fn parse_instruction_bytes(&mut self) -> String {
+        let stream: InstructionByteStream = match &self.crash_info.instruction_byte_stream {
+            Some(stream) => stream.clone(),
+            None => return String::new(),
+        };
+        let mut res: String = String::from("INSTRUCTION BYTES\n\n");
+
+        let (before, at) = match (
+            byte_stream::decode_base64(&stream.before_pc),
+            byte_stream::decode_base64(&stream.at_pc),
+        ) {
+            (Some(before), Some(at)) => (before, at),
+            _ => {
+                res.push_str("!!! instructionByteStream is not valid base64\n");
+                res.push_str(format!("{:-<20}\n\n", "").as_str());
+                return res;
+            }
+        };
+        let frame: Option<&Frame> = self
+            .crash_info
+            .threads
+            .iter()
+            .find(|thread| thread.triggered.is_some())
+            .and_then(|thread| thread.frames.first());
+        let (image, image_offset) = match frame.and_then(|frame| {
+            Some((self.crash_info.used_images.get(frame.image_index as usize)?, frame.image_offset))
+        }) {
+            Some((image, image_offset)) if image_offset >= before.len() as u64 => (image, image_offset),
+            _ => {
+                res.push_str("!!! No image for frame 0 of the crashing thread, the bytes can't be placed\n");
+                res.push_str(format!("{:-<20}\n\n", "").as_str());
+                return res;
+            }
+        };
+        let pc: u64 = image.base + image_offset;
+        let start: u64 = pc - before.len() as u64;
+        let mut bytes: Vec<u8> = before.clone();
+        bytes.extend(&at);
+
+        res.push_str(
+            format!(
+                "{:<20} {} bytes before pc, {} bytes at pc ({:#x})\n",
+                "Memory:",
+                before.len(),
+                at.len(),
+                pc
+            )
+            .as_str(),
+        );
+        let disk: Result<(String, Vec<u8>), String> =
+            self.disk_bytes(image, image_offset - before.len() as u64, bytes.len());
+        match &disk {
+            Ok((source, _)) => res.push_str(format!("{:<20} {}\n", "On disk:", source).as_str()),
+            Err(e) => {
+                println!("{}", e);
+                res.push_str(format!("!!! {}, showing the bytes of the report only\n", e).as_str());
+            }
+        }
+        res.push('\n');
+
+        // Both halves are disassembled on their own: on x86 the bytes before pc
+        // may start in the middle of an instruction
+        let arch: &str = if self.crash_info.cpu_type.starts_with("X86") {
+            "x86_64"
         } else {
-            let threads = self.crash_info.threads.clone();
-            threads.iter().for_each(|thread| {
-                if thread.triggered.is_some() {
-                    thread
-                        .frames
+            "arm64"
+        };
+        let mut r2: Option<R2Pipe> = match r2pipe_cache::open_bytes(&bytes, start, Some(arch)) {
+            Ok(r2) => Some(r2),
+            Err(e) => {
+                println!("Unable to disassemble the instruction bytes with r2: {}", e);
+                res.push_str(format!("!!! Unable to disassemble the instruction bytes with r2: {}\n", e).as_str());
+                None
+            }
+        };
+        let mut instructions: Vec<StreamInstruction> = Vec::new();
+        for (address, size) in [(start, before.len()), (pc, at.len())] {
+            let disassembled: Option<Vec<StreamInstruction>> = r2
+                .as_mut()
+                .and_then(|r2| byte_stream::disassemble(r2, address, size));
+            match disassembled {
+                Some(disassembled) => instructions.extend(disassembled),
+                // Raw words when r2 is missing
+                None => {
+                    let offset: usize = (address - start) as usize;
+                    instructions.extend(bytes[offset..offset + size].chunks(4).enumerate().map(
+                        |(n, chunk)| StreamInstruction {
+                            address: address + n as u64 * 4,
+                            bytes: chunk.to_vec(),
+                            text: String::new(),
+                        },
+                    ))
+                }
+            }
+        }
+
+        let mut differing: usize = 0;
+        for instruction in &instructions {
+            let mut line: String = format!(
+                "{:#x}  {:<16} {}",
+                instruction.address,
+                byte_stream::format_bytes(&instruction.bytes),
+                instruction.text
+            );
+            if instruction.address == pc {
+                line.push_str(format!("  {}", mark_label(0)).as_str());
+            }
+            if let Ok((_, disk)) = &disk {
+                let offset: usize = (instruction.address - start) as usize;
+                let on_disk: &[u8] = disk
+                    .get(offset..offset + instruction.bytes.len())
+                    .unwrap_or_default();
+                if on_disk != instruction.bytes.as_slice() {
+                    differing += instruction
+                        .bytes
                         .iter()
-                        .enumerate()
-                        .rev()
-                        .for_each(|(i, frame)| {
-                            res.push_str(self.get_frame_info(i, frame).as_str());
-                        });
+                        .zip(on_disk)
+                        .filter(|(memory, disk)| memory != disk)
+                        .count();
+                    line.push_str(format!("  !!! on disk: {}", byte_stream::format_bytes(on_disk)).as_str());
+                    if let Some(r2) = r2.as_mut() {
+                        line.push_str(format!(" ({})", byte_stream::disassemble_bytes(r2, on_disk)).as_str());
+                    }
                 }
-            });
+            }
+            res.push_str(self.demangler.demangle_text(line.trim_end()).as_str());
+            res.push('\n');
         }
 
+        if disk.is_ok() {
+            res.push('\n');
+            if differing == 0 {
+                res.push_str("The bytes in memory match the binary on disk\n");
+            } else {
+                res.push_str(
+                    format!(
+                        "!!! {} of {} bytes differ from the binary on disk: another build of the binary, \
+                         code patched at runtime (hook) or JIT code\n",
+                        differing,
+                        bytes.len()
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        res.push_str(format!("{:-<20}\n\n", "").as_str());
+
         res
     }
 
-    pub fn parse_registers(&self) -> String {
+    // Bytes of an image at an offset, read from the binary found on disk or
+    // from a dyld_shared_cache, with a description of where they come from
+    fn disk_bytes(&self, image: &UsedImage, image_offset: u64, size: usize) -> Result<(String, Vec<u8>), String> {
+        if let Some(binary) = self.locate_binary(image) {
+            let slice: &MachOSlice = binary
+                .slice
+                .as_ref()
+                .ok_or_else(|| format!("{} is not a readable Mach-O", binary.path))?;
+            let macho: MachOImage = MachOImage::load(Path::new(&binary.path), slice)
+                .map_err(|e| format!("Unable to read {}: {}", binary.path, e))?;
+            let address: u64 = macho.text_vmaddr().unwrap_or(0) + image_offset;
+            let bytes: &[u8] = macho
+                .read(address, size)
+                .ok_or_else(|| format!("{:#x} is not backed by {}", address, binary.path))?;
+
+            return Ok((format!("{} at {:#x}", binary.path, address), bytes.to_vec()));
+        }
+        if let Some(code) = self.locate_in_shared_cache(image, image_offset) {
+            let bytes: Vec<u8> =
+                byte_stream::read_mapped(Path::new(&code.file_path), &code.mappings, code.address, size)
+                    .map_err(|e| format!("Unable to read {}: {}", code.file_path, e))?;
+
+            return Ok((format!("{} at {:#x}", code.file_path, code.address), bytes));
+        }
+
+        Err(format!("{} not found in search paths", self.layout_path(image)))
+    }
+
+    // Formats the ARM64 register set. On arm64e, signed pointers are shown
+    // next to their canonical value and lr/pc are resolved like stack frames.
+    fn format_arm64_registers(&mut self, state: &Arm64ThreadState) -> String {
+        let mut res: String = String::new();
+
+        let pointer_auth: Option<PointerAuth> = self.pointer_auth;
+        let strip = |value: u64| pointer_auth.map_or(value, |p| p.strip(value));
+
+        state.x.iter().enumerate().for_each(|(i, r)| {
+            // General purpose registers may hold plain data: only show the
+            // stripped value when it points inside a loaded image
+            let canonical: Option<u64> =
+                Some(strip(r.value)).filter(|c| self.image_for_address(*c).is_some());
+            res.push_str(format_register(format!("x{i}").as_str(), r, canonical).as_str());
+        });
+
+        let pc: u64 = strip(state.pc.value);
+        let lr: u64 = strip(state.lr.value);
+
+        res.push_str(format_register("pc", &state.pc, Some(pc)).as_str());
+        if let Some(location) = self.resolve_code_address(pc) {
+            res.push_str(format!("    {location}\n").as_str());
+        }
+        res.push_str(format_register("sp", &state.sp, Some(strip(state.sp.value))).as_str());
+        res.push_str(format_register("fp", &state.fp, Some(strip(state.fp.value))).as_str());
+        res.push_str(format!("esr: {:#x}\n", state.esr.value).as_str());
+        res.push_str(format_register("lr", &state.lr, Some(lr)).as_str());
+        if let Some(location) = self.resolve_code_address(lr) {
+            res.push_str(format!("    {location}\n").as_str());
+        }
+        res.push_str(format!("cpsr: {:#x}\n", state.cpsr.value).as_str());
+        res.push_str(format_register("far", &state.far, Some(strip(state.far.value))).as_str());
+        res.push_str(format!("flavor: {}\n", state.flavor).as_str());
+
+        res.push_str(format_fault_analysis(state, strip(state.far.value)).as_str());
+
+        res
+    }
+
+    pub fn parse_registers(&mut self) -> String {
         let mut res: String = String::from("REGISTERS\n\n");
 
-        if self.crash_info.cpu_type == "X86-64" {
-        } else {
-            self.crash_info.threads.iter().for_each(|thread| {
-                if thread.triggered.is_some() {
-                    thread
-                        .thread_state
-                        .as_ref()
-                        .unwrap()
-                        .x
-                        .iter()
-                        .enumerate()
-                        .for_each(|(i, r)| {
-                            res.push_str(format!("x{i}: {:#x}", r.value).as_str());
-                            if r.objc_selector.is_some() {
-                                res.push_str(
-                                    format!("{:>50}\n", r.objc_selector.as_ref().unwrap()).as_str(),
-                                );
-                            } else if r.symbol.is_some() {
-                                res.push_str(
-                                    format!("{:>50}\n", r.symbol.as_ref().unwrap()).as_str(),
-                                );
-                            } else {
-                                res.push_str("\n");
-                            }
-                        });
-
-                    res.push_str(
-                        format!(
-                            "pc: {:#x}\n",
-                            thread.thread_state.as_ref().unwrap().pc.value
-                        )
-                        .as_str(),
-                    );
-                    res.push_str(
-                        format!(
-                            "sp: {:#x}\n",
-                            thread.thread_state.as_ref().unwrap().sp.value
-                        )
-                        .as_str(),
-                    );
-                    res.push_str(
-                        format!(
-                            "fp: {:#x}\n",
-                            thread.thread_state.as_ref().unwrap().fp.value
-                        )
-                        .as_str(),
-                    );
-                    res.push_str(
-                        format!(
-                            "esr: {:#x}\n",
-                            thread.thread_state.as_ref().unwrap().esr.value
-                        )
-                        .as_str(),
-                    );
-                    res.push_str(
-                        format!(
-                            "lr: {:#x}\n",
-                            thread.thread_state.as_ref().unwrap().lr.value
-                        )
-                        .as_str(),
-                    );
-                    res.push_str(
-                        format!(
-                            "cpsr: {:#x}\n",
-                            thread.thread_state.as_ref().unwrap().cpsr.value
-                        )
-                        .as_str(),
-                    );
-                    res.push_str(
-                        format!(
-                            "far: {:#x}\n",
-                            thread.thread_state.as_ref().unwrap().far.value
-                        )
-                        .as_str(),
-                    );
-                    res.push_str(
-                        format!("flavor: {}\n", thread.thread_state.as_ref().unwrap().flavor)
-                            .as_str(),
-                    );
+        let threads: Vec<Thread> = self.crash_info.threads.clone();
+        threads.iter().for_each(|thread| {
+            if thread.triggered.is_some() {
+                match thread.thread_state.as_ref() {
+                    Some(ThreadState::Arm64(state)) => {
+                        res.push_str(self.format_arm64_registers(state).as_str())
+                    }
+                    Some(ThreadState::X86_64(state)) => {
+                        res.push_str(format_x86_registers(state).as_str())
+                    }
+                    None => res.push_str("No thread state in report\n"),
                 }
-            });
-        }
+            }
+        });
 
         res.push_str(format!("{:-<20}\n\n", "").as_str());
 
@@ -0,0 +1,173 @@
+// Decoder for the ARM64 Exception Syndrome Register (ESR_EL1).
+//
+//  63      32 31    26  25  24                     0
+// +----------+--------+----+-----------------------+
+// |   RES0   |   EC   | IL |          ISS          |
+// +----------+--------+----+-----------------------+
+
+pub struct DecodedEsr {
+    pub exception_class: u8,
+    pub class_name: &'static str,
+    // Instruction length: true for a 32-bit instruction
+    pub il: bool,
+    pub iss: u32,
+    // Decoded ISS fields, as (label, value) pairs
+    pub details: Vec<(&'static str, String)>,
+    // Whether FAR holds the faulting address. None when the class doesn't set FAR.
+    pub far_valid: Option<bool>,
+}
+
+pub fn decode(esr: u64) -> DecodedEsr {
+    let exception_class: u8 = ((esr >> 26) & 0x3f) as u8;
+    let il: bool = (esr >> 25) & 1 == 1;
+    let iss: u32 = (esr & 0x1ff_ffff) as u32;
+
+    let mut details: Vec<(&'static str, String)> = Vec::new();
+    let mut far_valid: Option<bool> = None;
+
+    match exception_class {
+        // Data abort
+        0x24 | 0x25 => {
+            let fnv: bool = (iss >> 10) & 1 == 1;
+            let wnr: bool = (iss >> 6) & 1 == 1;
+            let cm: bool = (iss >> 8) & 1 == 1;
+            let dfsc: u8 = (iss & 0x3f) as u8;
+
+            details.push((
+                "Access:",
+                format!(
+                    "{} (WnR={})",
+                    if cm {
+                        "cache maintenance"
+                    } else if wnr {
+                        "write"
+                    } else {
+                        "read"
+                    },
+                    wnr as u8
+                ),
+            ));
+            if (iss >> 24) & 1 == 1 {
+                let size: u32 = 1 << ((iss >> 22) & 0x3);
+                let register: u32 = (iss >> 16) & 0x1f;
+                let width: &str = if (iss >> 15) & 1 == 1 { "x" } else { "w" };
+                details.push(("Access size:", format!("{size} byte(s)")));
+                details.push(("Transfer reg:", format!("{width}{register}")));
+            }
+            details.push(("Fault status:", format!("{:#04x} {}", dfsc, fault_status(dfsc))));
+            if (iss >> 9) & 1 == 1 {
+                details.push(("External abort:", String::from("yes")));
+            }
+            if (iss >> 7) & 1 == 1 {
+                details.push(("Stage 2 walk:", String::from("fault on stage 1 translation table walk")));
+            }
+            far_valid = Some(!fnv);
+        }
+        // Instruction abort
+        0x20 | 0x21 => {
+            let fnv: bool = (iss >> 10) & 1 == 1;
+            let ifsc: u8 = (iss & 0x3f) as u8;
+
+            details.push(("Fault status:", format!("{:#04x} {}", ifsc, fault_status(ifsc))));
+            far_valid = Some(!fnv);
+        }
+        // BRK instruction
+        0x3c => {
+            let comment: u32 = iss & 0xffff;
+            details.push(("BRK comment:", format!("{:#x} {}", comment, brk_comment(comment))));
+        }
+        // Pointer authentication failure (FEAT_FPAC)
+        0x1c => {
+            let key: &str = match (((iss >> 1) & 1), iss & 1) {
+                (0, 0) => "IA",
+                (0, 1) => "IB",
+                (1, 0) => "DA",
+                _ => "DB",
+            };
+            details.push(("PAC key:", key.to_string()));
+        }
+        // SVC
+        0x15 => {
+            details.push(("SVC immediate:", format!("{:#x}", iss & 0xffff)));
+        }
+        // Watchpoint
+        0x34 | 0x35 => {
+            let wnr: bool = (iss >> 6) & 1 == 1;
+            details.push(("Access:", String::from(if wnr { "write" } else { "read" })));
+            far_valid = Some(true);
+        }
+        // PC alignment fault
+        0x22 => far_valid = Some(true),
+        _ => {}
+    }
+
+    DecodedEsr {
+        exception_class,
+        class_name: class_name(exception_class),
+        il,
+        iss,
+        details,
+        far_valid,
+    }
+}
+
+fn class_name(exception_class: u8) -> &'static str {
+    match exception_class {
+        0x00 => "Unknown reason",
+        0x01 => "Trapped WFI/WFE instruction",
+        0x07 => "Trapped SIMD/floating-point access",
+        0x0e => "Illegal execution state",
+        0x15 => "SVC instruction",
+        0x18 => "Trapped MSR/MRS/system instruction",
+        0x19 => "Trapped SVE access",
+        0x1c => "Pointer authentication failure",
+        0x20 => "Instruction abort from a lower exception level",
+        0x21 => "Instruction abort from the same exception level",
+        0x22 => "PC alignment fault",
+        0x24 => "Data abort from a lower exception level",
+        0x25 => "Data abort from the same exception level",
+        0x26 => "SP alignment fault",
+        0x2c => "Trapped floating-point exception",
+        0x2f => "SError interrupt",
+        0x30 | 0x31 => "Hardware breakpoint",
+        0x32 | 0x33 => "Software step",
+        0x34 | 0x35 => "Watchpoint",
+        0x3c => "BRK instruction",
+        _ => "Reserved/unhandled exception class",
+    }
+}
+
+// Data/Instruction Fault Status Code
+fn fault_status(code: u8) -> String {
+    match code {
+        0x00..=0x03 => format!("Address size fault, level {}", code & 0x3),
+        0x04..=0x07 => format!("Translation fault, level {}", code & 0x3),
+        0x08..=0x0b => format!("Access flag fault, level {}", code & 0x3),
+        0x0c..=0x0f => format!("Permission fault, level {}", code & 0x3),
+        0x10 => String::from("Synchronous external abort"),
+        0x11 => String::from("Synchronous tag check fault (MTE)"),
+        0x14..=0x17 => format!(
+            "Synchronous external abort on translation table walk, level {}",
+            code & 0x3
+        ),
+        0x18 => String::from("Synchronous parity or ECC error"),
+        0x21 => String::from("Alignment fault"),
+        0x30 => String::from("TLB conflict abort"),
+        0x31 => String::from("Unsupported atomic hardware update fault"),
+        0x34 => String::from("Lockdown (implementation defined)"),
+        0x35 => String::from("Unsupported exclusive or atomic access (implementation defined)"),
+        _ => String::from("Reserved"),
+    }
+}
+
+// Well-known BRK immediates emitted by Apple toolchains
+fn brk_comment(comment: u32) -> &'static str {
+    match comment {
+        0x1 => "(Swift runtime failure or __builtin_trap)",
+        0xc470 => "(pointer authentication failure, IA key)",
+        0xc471 => "(pointer authentication failure, IB key)",
+        0xc472 => "(pointer authentication failure, DA key)",
+        0xc473 => "(pointer authentication failure, DB key)",
+        _ => "",
+    }
+}
@@ -0,0 +1,321 @@
+use crate::structs::crash_info::Exception;
+
+// Decoder for the Mach exception codes (`exception.rawCodes`) of a report.
+// The meaning of each code depends on the exception type, see
+// osfmk/mach/exception_types.h, osfmk/mach/arm/exception.h,
+// osfmk/kern/exc_guard.h and osfmk/kern/exc_resource.h in XNU.
+//
+// Returns the decoded fields as (label, value) pairs, empty when there is
+// nothing to decode.
+pub fn decode(exception: &Exception, is_x86: bool) -> Vec<(&'static str, String)> {
+    let mut details: Vec<(&'static str, String)> = Vec::new();
+
+    let code: u64 = match exception.raw_codes.first() {
+        Some(code) => *code,
+        None => return details,
+    };
+    let subcode: Option<u64> = exception.raw_codes.get(1).copied();
+
+    match exception.r#type.as_str() {
+        "EXC_BAD_ACCESS" => {
+            details.push(("Reason:", bad_access_code(code, is_x86)));
+            if let Some(address) = subcode {
+                details.push(("Address:", format!("{:#x}", address)));
+                if address < 0x4000 {
+                    details.push((
+                        "Hint:",
+                        String::from("address is in the NULL page (NULL pointer dereference?)"),
+                    ));
+                }
+            }
+        }
+        "EXC_BAD_INSTRUCTION" => {
+            let reason: &str = match (code, is_x86) {
+                (1, false) => "EXC_ARM_UNDEFINED (undefined instruction)",
+                (1, true) => "EXC_I386_INVOP (invalid opcode)",
+                _ => "unknown",
+            };
+            details.push(("Reason:", reason.to_string()));
+            if let Some(instruction) = subcode {
+                details.push(("Instruction:", format!("{:#x}", instruction)));
+            }
+        }
+        "EXC_ARITHMETIC" => {
+            let reason: &str = match (code, is_x86) {
+                (1, true) => "EXC_I386_DIV (integer divide by zero)",
+                (2, true) => "EXC_I386_INTO (integer overflow)",
+                (3, true) => "EXC_I386_NOEXT",
+                (4, true) => "EXC_I386_EXTOVR",
+                (5, true) => "EXC_I386_EXTERR (floating point error)",
+                (6, true) => "EXC_I386_EMERR",
+                (7, true) => "EXC_I386_BOUND",
+                (8, true) => "EXC_I386_SSEEXTERR (SSE floating point error)",
+                (1, false) => "EXC_ARM_FP_IO (invalid operation)",
+                (2, false) => "EXC_ARM_FP_DZ (divide by zero)",
+                (3, false) => "EXC_ARM_FP_OF (overflow)",
+                (4, false) => "EXC_ARM_FP_UF (underflow)",
+                (5, false) => "EXC_ARM_FP_IX (inexact)",
+                (6, false) => "EXC_ARM_FP_ID (input denormal)",
+                _ => "unknown",
+            };
+            details.push(("Reason:", reason.to_string()));
+        }
+        "EXC_BREAKPOINT" => {
+            let reason: &str = match (code, is_x86) {
+                (1, false) => "EXC_ARM_BREAKPOINT (trap instruction, e.g. brk)",
+                (1, true) => "EXC_I386_SGL (single step)",
+                (2, true) => "EXC_I386_BPT (breakpoint)",
+                _ => "unknown",
+            };
+            details.push(("Reason:", reason.to_string()));
+            if let Some(pc) = subcode {
+                details.push(("Trapping PC:", format!("{:#x}", pc)));
+            }
+        }
+        "EXC_GUARD" => decode_guard(code, subcode, &mut details),
+        "EXC_RESOURCE" => decode_resource(code, subcode, &mut details),
+        "EXC_CRASH" => {
+            // bsd/kern/kern_exit.c encodes the signal and the original exception in the code
+            let signal: u64 = (code >> 24) & 0xff;
+            let original: u64 = (code >> 20) & 0xf;
+            if signal != 0 {
+                details.push(("Signal:", format!("{} ({})", signal_name(signal), signal)));
+            }
+            if original != 0 {
+                details.push(("Original exception:", exception_name(original).to_string()));
+            }
+        }
+        _ => {}
+    }
+
+    details
+}
+
+fn bad_access_code(code: u64, is_x86: bool) -> String {
+    let reason: &str = match code {
+        1 => "KERN_INVALID_ADDRESS (address is not mapped)",
+        2 => "KERN_PROTECTION_FAILURE (access not allowed by page protections)",
+        9 => "KERN_MEMORY_FAILURE",
+        10 => "KERN_MEMORY_ERROR",
+        50 => "KERN_CODESIGN_ERROR (invalid code signature for the page)",
+        13 if is_x86 => "EXC_I386_GPFLT (general protection fault, non-canonical address?)",
+        0x101 if !is_x86 => "EXC_ARM_DA_ALIGN (unaligned data access)",
+        0x102 if !is_x86 => "EXC_ARM_DA_DEBUG (debug exception)",
+        0x103 if !is_x86 => "EXC_ARM_SP_ALIGN (unaligned stack pointer)",
+        0x104 if !is_x86 => "EXC_ARM_SWP (SWP instruction)",
+        0x105 if !is_x86 => "EXC_ARM_PAC_FAIL (pointer authentication failure)",
+        _ => "unknown",
+    };
+
+    format!("{:#x} {}", code, reason)
+}
+
+// EXC_GUARD code layout:
+//  63   61 60               32 31                0
+// +-------+-------------------+-------------------+
+// | type  |      flavor       |      target       |
+// +-------+-------------------+-------------------+
+fn decode_guard(code: u64, subcode: Option<u64>, details: &mut Vec<(&'static str, String)>) {
+    let guard_type: u64 = (code >> 61) & 0x7;
+    let flavor: u64 = (code >> 32) & 0x1fff_ffff;
+    let target: u64 = code & 0xffff_ffff;
+
+    let (type_name, target_label, flavors): (&str, &'static str, &[(u64, &str)]) = match guard_type
+    {
+        1 => (
+            "GUARD_TYPE_MACH_PORT",
+            "Port name:",
+            &[
+                (1 << 0, "kGUARD_EXC_DESTROY"),
+                (1 << 1, "kGUARD_EXC_MOD_REFS"),
+                (1 << 2, "kGUARD_EXC_INVALID_OPTIONS"),
+                (1 << 3, "kGUARD_EXC_UNGUARDED"),
+                (1 << 4, "kGUARD_EXC_INCORRECT_GUARD"),
+                (1 << 5, "kGUARD_EXC_IMMOVABLE"),
+                (1 << 6, "kGUARD_EXC_STRICT_REPLY"),
+                (1 << 7, "kGUARD_EXC_MSG_FILTERED"),
+                (1 << 8, "kGUARD_EXC_INVALID_RIGHT"),
+                (1 << 9, "kGUARD_EXC_INVALID_NAME"),
+                (1 << 10, "kGUARD_EXC_INVALID_VALUE"),
+                (1 << 11, "kGUARD_EXC_INVALID_ARGUMENT"),
+                (1 << 12, "kGUARD_EXC_RIGHT_EXISTS"),
+                (1 << 13, "kGUARD_EXC_KERN_NO_SPACE"),
+                (1 << 14, "kGUARD_EXC_KERN_FAILURE"),
+                (1 << 15, "kGUARD_EXC_KERN_RESOURCE"),
+            ],
+        ),
+        2 => (
+            "GUARD_TYPE_FD",
+            "File descriptor:",
+            &[
+                (1 << 0, "GUARD_CLOSE"),
+                (1 << 1, "GUARD_DUP"),
+                (1 << 2, "GUARD_NOCLOEXEC"),
+                (1 << 3, "GUARD_SOCKET_IPC"),
+                (1 << 4, "GUARD_FILEPORT"),
+                (1 << 5, "GUARD_WRITE"),
+            ],
+        ),
+        3 => ("GUARD_TYPE_USER", "Namespace:", &[]),
+        4 => (
+            "GUARD_TYPE_VN",
+            "PID:",
+            &[
+                (1 << 0, "kGUARD_EXC_RENAME_TO"),
+                (1 << 1, "kGUARD_EXC_RENAME_FROM"),
+                (1 << 2, "kGUARD_EXC_UNLINK"),
+                (1 << 3, "kGUARD_EXC_WRITE_OTHER"),
+                (1 << 4, "kGUARD_EXC_TRUNC_OTHER"),
+                (1 << 5, "kGUARD_EXC_LINK"),
+                (1 << 6, "kGUARD_EXC_EXCHDATA"),
+            ],
+        ),
+        5 => (
+            "GUARD_TYPE_VIRT_MEMORY",
+            "Target:",
+            &[(1 << 0, "kGUARD_EXC_DEALLOC_GAP")],
+        ),
+        6 => ("GUARD_TYPE_REJECTED_SC", "Syscall:", &[]),
+        _ => ("unknown", "Target:", &[]),
+    };
+
+    details.push(("Guard type:", format!("{} ({})", type_name, guard_type)));
+
+    let flavor_names: Vec<&str> = flavors
+        .iter()
+        .filter(|(bit, _)| flavor & bit != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    if flavor_names.is_empty() {
+        details.push(("Flavor:", format!("{:#x}", flavor)));
+    } else {
+        details.push((
+            "Flavor:",
+            format!("{} ({:#x})", flavor_names.join(" | "), flavor),
+        ));
+    }
+
+    details.push((target_label, format!("{:#x}", target)));
+    if let Some(identifier) = subcode {
+        details.push(("Guard identifier:", format!("{:#x}", identifier)));
+    }
+}
+
+// EXC_RESOURCE code layout:
+//  63   61 60   58 57                            0
+// +-------+-------+-------------------------------+
+// | type  |flavor |        type specific          |
+// +-------+-------+-------------------------------+
+fn decode_resource(code: u64, subcode: Option<u64>, details: &mut Vec<(&'static str, String)>) {
+    let resource_type: u64 = (code >> 61) & 0x7;
+    let flavor: u64 = (code >> 58) & 0x7;
+    let observed: u64 = subcode.unwrap_or_default();
+
+    match resource_type {
+        1 => {
+            details.push((
+                "Resource:",
+                match flavor {
+                    2 => String::from("CPU (FLAVOR_CPU_MONITOR_FATAL)"),
+                    _ => String::from("CPU (FLAVOR_CPU_MONITOR)"),
+                },
+            ));
+            details.push((
+                "Limit:",
+                format!(
+                    "{}% CPU over {} seconds",
+                    code & 0x7f,
+                    (code >> 7) & 0x1ff_ffff
+                ),
+            ));
+            details.push(("Observed:", format!("{}% CPU", observed & 0x7f)));
+        }
+        2 => {
+            details.push((
+                "Resource:",
+                String::from("wakeups (FLAVOR_WAKEUPS_MONITOR)"),
+            ));
+            details.push(("Limit:", format!("{} wakeups/second", code & 0xfff)));
+            details.push(("Observed:", format!("{} wakeups/second", observed & 0xfff)));
+        }
+        3 => {
+            details.push(("Resource:", String::from("memory (FLAVOR_HIGH_WATERMARK)")));
+            details.push(("Limit:", format!("{} MB", code & 0x1fff)));
+        }
+        4 => {
+            details.push((
+                "Resource:",
+                match flavor {
+                    2 => String::from("disk writes (FLAVOR_IO_LOGICAL_WRITES)"),
+                    _ => String::from("disk writes (FLAVOR_IO_PHYSICAL_WRITES)"),
+                },
+            ));
+            details.push((
+                "Limit:",
+                format!(
+                    "{} MB over {} seconds",
+                    code & 0x7fff,
+                    (code >> 15) & 0x1_ffff
+                ),
+            ));
+            details.push(("Observed:", format!("{} MB", observed & 0x7fff)));
+        }
+        5 => {
+            details.push((
+                "Resource:",
+                String::from("threads (FLAVOR_THREADS_HIGH_WATERMARK)"),
+            ));
+            details.push(("Limit:", format!("{} threads", code & 0x7fff)));
+        }
+        6 => {
+            details.push((
+                "Resource:",
+                String::from("Mach ports (FLAVOR_PORT_SPACE_FULL)"),
+            ));
+            details.push(("Limit:", format!("{} ports", code & 0xff_ffff)));
+        }
+        _ => details.push(("Resource:", format!("unknown ({})", resource_type))),
+    }
+}
+
+fn exception_name(exception: u64) -> &'static str {
+    match exception {
+        1 => "EXC_BAD_ACCESS",
+        2 => "EXC_BAD_INSTRUCTION",
+        3 => "EXC_ARITHMETIC",
+        4 => "EXC_EMULATION",
+        5 => "EXC_SOFTWARE",
+        6 => "EXC_BREAKPOINT",
+        7 => "EXC_SYSCALL",
+        8 => "EXC_MACH_SYSCALL",
+        9 => "EXC_RPC_ALERT",
+        10 => "EXC_CRASH",
+        11 => "EXC_RESOURCE",
+        12 => "EXC_GUARD",
+        13 => "EXC_CORPSE_NOTIFY",
+        _ => "unknown",
+    }
+}
+
+pub fn signal_name(signal: u64) -> &'static str {
+    match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        7 => "SIGEMT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        10 => "SIGBUS",
+        11 => "SIGSEGV",
+        12 => "SIGSYS",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        24 => "SIGXCPU",
+        25 => "SIGXFSZ",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,3 @@
+pub mod esr;
+pub mod mach_exception;
+pub mod termination;
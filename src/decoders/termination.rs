@@ -0,0 +1,76 @@
+use super::mach_exception::signal_name;
+use crate::structs::crash_info::Termination;
+
+// Maps a termination namespace/code pair to a human explanation.
+// Returns None when the pair is not a well-known one.
+pub fn explain(termination: &Termination) -> Option<String> {
+    let code: u64 = termination.code as u64;
+
+    match termination.namespace.as_str() {
+        "SIGNAL" => Some(format!(
+            "Terminated by signal {} ({})",
+            signal_name(code),
+            code
+        )),
+        "FRONTBOARD" | "SPRINGBOARD" | "RUNNINGBOARD" | "ASSERTIOND" => {
+            scene_manager_code(code).map(|s| s.to_string())
+        }
+        "CODESIGNING" => Some(
+            match code {
+                1 => "Invalid signature (taskgated): the code signature of the process was rejected",
+                2 => {
+                    "Invalid page: a code page failed code signature validation \
+                      (executable memory that is not validly signed)"
+                }
+                _ => "Code signing violation",
+            }
+            .to_string(),
+        ),
+        "DYLD" => Some(String::from(
+            "The dynamic linker failed to launch the process (missing library or symbol)",
+        )),
+        "JETSAM" | "MEMORY" => Some(String::from(
+            "Killed by jetsam: the system reclaimed memory (memory limit or memory pressure)",
+        )),
+        "WATCHDOG" => Some(String::from(
+            "Killed by the watchdog daemon: the process stopped responding to its monitor",
+        )),
+        "SANDBOX" => Some(String::from("Sandbox violation")),
+        "EXEC" => Some(String::from("The kernel failed to execute the binary")),
+        "OBJC" => Some(String::from("The Objective-C runtime aborted the process")),
+        _ => None,
+    }
+}
+
+// Codes used by the app life cycle managers (FrontBoard, SpringBoard, RunningBoard)
+fn scene_manager_code(code: u64) -> Option<&'static str> {
+    match code {
+        0x8badf00d => Some(
+            "0x8badf00d \"ate bad food\": watchdog timeout, the app took too long \
+             to launch, resume, suspend or respond to a system event",
+        ),
+        0xdead10cc => Some(
+            "0xdead10cc \"deadlock\": the app held a file lock or SQLite database \
+             lock while being suspended",
+        ),
+        0xbaadca11 => Some(
+            "0xbaadca11 \"bad call\": the app failed to report a CallKit call in \
+             response to a PushKit VoIP notification",
+        ),
+        0xbad22222 => Some("0xbad22222: the VoIP app was resumed too frequently"),
+        0xc00010ff => Some("0xc00010ff \"cool off\": killed in response to a thermal event"),
+        0xdeadfa11 => Some("0xdeadfa11 \"dead fall\": the user force quit the app"),
+        0x2bad45ec => {
+            Some("0x2bad45ec: security violation, unsafe drawing while in navigation mode")
+        }
+        0xc51bad01 => {
+            Some("0xc51bad01: watchOS, too much CPU time while running in the background")
+        }
+        0xc51bad02 => Some("0xc51bad02: watchOS, background task did not complete in time"),
+        0xc51bad03 => Some(
+            "0xc51bad03: watchOS, background task did not complete in time \
+             (system was busy)",
+        ),
+        _ => None,
+    }
+}
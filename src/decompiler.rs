@@ -0,0 +1,114 @@
+use lazy_static::lazy_static;
+use r2pipe::R2Pipe;
+use regex::Regex;
+
+// Decompiler choice picking the first one available
+pub const AUTO: &str = "auto";
+
+lazy_static! {
+    // Address printed at the start of the lines of decompiled code
+    static ref LINE_ADDRESS: Regex = Regex::new(r"^\s*0x([0-9A-Fa-f]+)").unwrap();
+}
+
+// A decompiler run through r2
+pub struct Decompiler {
+    pub command: &'static str,
+    // Variant printing the address of each line, to mark the crash line
+    command_with_offsets: &'static str,
+    // r2 core plugin providing the command, None for the built-in one
+    plugin: Option<&'static str>,
+}
+
+// In order of preference: r2ghidra, r2dec, then r2's own pdc
+const DECOMPILERS: &[Decompiler] = &[
+    Decompiler {
+        command: "pdg",
+        command_with_offsets: "pdgo",
+        plugin: Some("r2ghidra"),
+    },
+    Decompiler {
+        command: "pdd",
+        command_with_offsets: "pddo",
+        plugin: Some("r2dec"),
+    },
+    Decompiler {
+        command: "pdc",
+        command_with_offsets: "pdc",
+        plugin: None,
+    },
+];
+
+pub fn is_known(choice: &str) -> bool {
+    choice == AUTO || DECOMPILERS.iter().any(|d| d.command == choice)
+}
+
+// The decompiler to use in a r2 session: the chosen one when its plugin is
+// loaded, or the first available one with "auto"
+pub fn select(r2: &mut R2Pipe, choice: &str) -> Option<&'static Decompiler> {
+    let plugins: String = r2.cmd("Lc").unwrap_or_default();
+
+    DECOMPILERS
+        .iter()
+        .filter(|d| choice == AUTO || d.command == choice)
+        .find(|d| d.plugin.is_none_or(|plugin| plugins.contains(plugin)))
+}
+
+impl Decompiler {
+    // Decompiles the function containing `marked` and marks the line holding
+    // it: the last line whose address is not after it. None when the
+    // decompiler prints nothing (no function, unsupported code).
+    pub fn decompile(
+        &self,
+        r2: &mut R2Pipe,
+        function_start: Option<u64>,
+        marked: u64,
+        label: &str,
+    ) -> Option<String> {
+        // Code mapped from a dyld_shared_cache is not analyzed up front
+        if let Some(start) = function_start {
+            r2.cmd(format!("af @ {:#x}", start).as_str()).ok()?;
+        }
+        let code: String = r2
+            .cmd(format!("{} @ {:#x}", self.command_with_offsets, marked).as_str())
+            .ok()?;
+        if code.trim().is_empty() {
+            return None;
+        }
+
+        let addresses: Vec<Option<u64>> = code
+            .lines()
+            .map(|line| {
+                LINE_ADDRESS
+                    .captures(line)
+                    .and_then(|c| u64::from_str_radix(c.get(1).unwrap().as_str(), 16).ok())
+            })
+            .collect();
+        let crash_line: Option<usize> = addresses
+            .iter()
+            .enumerate()
+            .filter_map(|(n, address)| Some((n, (*address)?)))
+            .filter(|(_, address)| *address <= marked)
+            .max_by_key(|(_, address)| *address)
+            .map(|(n, _)| n);
+
+        let mut res: String = format!("[{}]\n", self.command);
+        for (n, line) in code.trim_end().lines().enumerate() {
+            if Some(n) == crash_line {
+                res.push_str(format!("{}  {}\n", line, label).as_str());
+            } else {
+                res.push_str(format!("{}\n", line).as_str());
+            }
+        }
+        if crash_line.is_none() {
+            res.push_str(
+                format!(
+                    "!!! {} printed no addresses, the line at {:#x} is not marked\n",
+                    self.command, marked
+                )
+                .as_str(),
+            );
+        }
+
+        Some(res)
+    }
+}
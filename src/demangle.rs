@@ -0,0 +1,221 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Programs used when none is configured. Both read one name per line on stdin
+// and print the demangled name on the same line.
+pub const DEFAULT_SWIFT_DEMANGLER: &str = "swift-demangle";
+pub const DEFAULT_CXX_DEMANGLER: &str = "c++filt";
+
+lazy_static! {
+    // A mangled Swift or C++ name in free text (r2 listings): "$s...", "_$s...",
+    // "_T0..." (Swift 4), "_Z..." and "__Z..." (Mach-O C++), with the text
+    // before it so that names inside identifiers are left alone
+    static ref MANGLED_NAME: Regex =
+        Regex::new(r"(^|[^A-Za-z0-9_$])(_?_?(?:\$[sSe]|_T0|_Z)[A-Za-z0-9_$.]+)").unwrap();
+}
+
+// Why a run of a tool gave no names
+enum RunError {
+    // The tool could not run: it is not tried again
+    Unavailable,
+    // Its output did not have one line per name
+    Mismatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+    Swift,
+    Cxx,
+}
+
+fn language(name: &str) -> Option<Language> {
+    let unprefixed: &str = name.strip_prefix('_').unwrap_or(name);
+
+    if unprefixed.starts_with("$s")
+        || unprefixed.starts_with("$S")
+        || unprefixed.starts_with("$e")
+        || name.starts_with("_T0")
+    {
+        Some(Language::Swift)
+    } else if unprefixed.starts_with("_Z") || name.starts_with("_Z") {
+        Some(Language::Cxx)
+    } else {
+        None
+    }
+}
+
+// Demangles Swift and C++ names with external tools (swift-demangle, c++filt),
+// caching the results. Names are returned unchanged when demangling is turned
+// off or the tool is not available.
+pub struct Demangler {
+    keep_mangled: bool,
+    swift_program: String,
+    cxx_program: String,
+    cache: HashMap<String, String>,
+    // Tools that failed to run, not tried again
+    failed: Vec<Language>,
+}
+
+impl Demangler {
+    pub fn new(keep_mangled: bool, swift_program: &str, cxx_program: &str) -> Self {
+        Self {
+            keep_mangled,
+            swift_program: swift_program.to_string(),
+            cxx_program: cxx_program.to_string(),
+            cache: HashMap::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    pub fn demangle(&mut self, name: &str) -> String {
+        if self.keep_mangled || language(name).is_none() {
+            return name.to_string();
+        }
+        self.prefetch(&[name]);
+
+        self.cache
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    // Demangles several names with one run of each tool
+    pub fn prefetch(&mut self, names: &[&str]) {
+        if self.keep_mangled {
+            return;
+        }
+
+        for language in [Language::Swift, Language::Cxx] {
+            let mut pending: Vec<&str> = names
+                .iter()
+                .copied()
+                .filter(|name| self::language(name) == Some(language))
+                .filter(|name| !self.cache.contains_key(*name))
+                .collect();
+            pending.sort();
+            pending.dedup();
+            if pending.is_empty() || self.failed.contains(&language) {
+                continue;
+            }
+
+            if let Err(RunError::Unavailable) = self.demangle_batch(language, &pending) {
+                self.failed.push(language);
+            }
+        }
+    }
+
+    // Demangles a batch of names with one run of the tool. When the output does
+    // not line up with the input (a name the tool reads as several lines, or
+    // skips), the batch is split in halves until it does; names the tool can't
+    // handle alone stay mangled.
+    fn demangle_batch(&mut self, language: Language, names: &[&str]) -> Result<(), RunError> {
+        match self.run(language, names) {
+            Ok(demangled) => {
+                for (name, demangled) in names.iter().zip(demangled) {
+                    self.cache.insert(name.to_string(), demangled);
+                }
+                Ok(())
+            }
+            Err(RunError::Mismatch) if names.len() > 1 => {
+                let (first, second) = names.split_at(names.len() / 2);
+                self.demangle_batch(language, first)?;
+                self.demangle_batch(language, second)
+            }
+            Err(RunError::Mismatch) => {
+                self.cache.insert(names[0].to_string(), names[0].to_string());
+                Ok(())
+            }
+            Err(RunError::Unavailable) => Err(RunError::Unavailable),
+        }
+    }
+
+    // Replaces the mangled names found in a text, like a r2 listing
+    pub fn demangle_text(&mut self, text: &str) -> String {
+        if self.keep_mangled {
+            return text.to_string();
+        }
+
+        let names: Vec<&str> = MANGLED_NAME
+            .captures_iter(text)
+            .map(|c| c.get(2).unwrap().as_str())
+            .collect();
+        if names.is_empty() {
+            return text.to_string();
+        }
+        self.prefetch(&names);
+
+        MANGLED_NAME
+            .replace_all(text, |c: &Captures| {
+                let name: &str = c.get(2).unwrap().as_str();
+                format!(
+                    "{}{}",
+                    c.get(1).unwrap().as_str(),
+                    self.cache.get(name).map_or(name, |d| d.as_str())
+                )
+            })
+            .to_string()
+    }
+
+    fn run(&self, language: Language, names: &[&str]) -> Result<Vec<String>, RunError> {
+        let (program, args, input): (&str, &[&str], Vec<&str>) = match language {
+            Language::Swift => (
+                self.swift_program.as_str(),
+                &["--simplified", "--compact"],
+                names.to_vec(),
+            ),
+            // Mach-O C++ symbols carry an extra leading underscore
+            Language::Cxx => (
+                self.cxx_program.as_str(),
+                &[],
+                names
+                    .iter()
+                    .map(|name| {
+                        if name.starts_with("__Z") {
+                            &name[1..]
+                        } else {
+                            name
+                        }
+                    })
+                    .collect(),
+            ),
+        };
+
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                println!("Unable to run {}: {}, names stay mangled", program, e);
+                return Err(RunError::Unavailable);
+            }
+        };
+
+        let mut stdin = child.stdin.take().ok_or(RunError::Unavailable)?;
+        let input: String = input.join("\n") + "\n";
+        // Written from another thread: the tool may fill its stdout before
+        // reading all of its input
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output().map_err(|_| RunError::Unavailable)?;
+        let written: bool = matches!(writer.join(), Ok(Ok(())));
+        if !output.status.success() {
+            return Err(RunError::Unavailable);
+        }
+
+        let lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect();
+        if !written || lines.len() != names.len() {
+            return Err(RunError::Mismatch);
+        }
+
+        Ok(lines)
+    }
+}
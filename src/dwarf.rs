@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Program used to read the DWARF of dSYM files when none is configured
+pub const DEFAULT_SYMBOLIZER: &str = "llvm-symbolizer";
+
+// A source location from the DWARF line table
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+    pub function: String,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl SourceLocation {
+    pub fn file_and_line(&self) -> String {
+        match self.column {
+            0 => format!("{}:{}", self.file, self.line),
+            column => format!("{}:{}:{}", self.file, self.line, column),
+        }
+    }
+}
+
+// Reads the DWARF of dSYM companion files with llvm-symbolizer
+pub struct Symbolizer {
+    program: String,
+    // Source files read so far, by path (None when unreadable)
+    sources: HashMap<String, Option<Vec<String>>>,
+}
+
+impl Symbolizer {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            sources: HashMap::new(),
+        }
+    }
+
+    // Looks up unslid addresses in a dSYM DWARF file. For each address, returns
+    // the inlined call chain, innermost function first, or an empty chain when the
+    // address has no debug information.
+    pub fn symbolize(
+        &self,
+        dwarf_file: &Path,
+        arch: &str,
+        addresses: &[u64],
+    ) -> io::Result<Vec<Vec<SourceLocation>>> {
+        if addresses.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let output: Output = Command::new(&self.program)
+            .arg(format!("--obj={}", dwarf_file.display()))
+            .arg(format!("--default-arch={}", arch))
+            .arg("--inlining")
+            .arg("--functions=linkage")
+            .args(addresses.iter().map(|address| format!("{:#x}", address)))
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+
+        Ok(parse_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    // Returns a line of a source file when the file is readable on this machine
+    pub fn source_line(&mut self, file: &str, line: u32) -> Option<String> {
+        let lines: &Option<Vec<String>> =
+            self.sources.entry(file.to_string()).or_insert_with(|| {
+                read_to_string(file)
+                    .ok()
+                    .map(|content| content.lines().map(|l| l.to_string()).collect())
+            });
+
+        lines
+            .as_ref()?
+            .get((line as usize).checked_sub(1)?)
+            .map(|l| l.trim().to_string())
+    }
+}
+
+// llvm-symbolizer prints, for each address, pairs of "function" and
+// "file:line:column" lines (one pair per inlined frame) followed by an empty line
+fn parse_output(output: &str) -> Vec<Vec<SourceLocation>> {
+    output
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| {
+            let lines: Vec<&str> = block.lines().filter(|l| !l.is_empty()).collect();
+            lines
+                .chunks(2)
+                .filter_map(|pair| parse_location(pair[0], pair.get(1)?))
+                .collect()
+        })
+        .collect()
+}
+
+fn parse_location(function: &str, location: &str) -> Option<SourceLocation> {
+    // Paths may contain ':', line and column are the last two fields
+    let mut fields = location.rsplitn(3, ':');
+    let column: u32 = fields.next()?.parse().ok()?;
+    let line: u32 = fields.next()?.parse().ok()?;
+    let file: &str = fields.next()?;
+
+    if file == "??" || line == 0 {
+        return None;
+    }
+
+    Some(SourceLocation {
+        function: function.to_string(),
+        file: file.to_string(),
+        line,
+        column,
+    })
+}
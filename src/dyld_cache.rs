@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::macho::format_uuid;
+
+const DYLD_CACHE_MAGIC: &[u8] = b"dyld_v1";
+
+// Offsets in dyld_cache_header
+const MAPPING_OFFSET: usize = 0x10;
+const MAPPING_COUNT: usize = 0x14;
+const UUID_OFFSET: usize = 0x58;
+const IMAGES_TEXT_OFFSET: usize = 0x88;
+const IMAGES_TEXT_COUNT: usize = 0x90;
+const HEADER_SIZE: usize = 0x200;
+
+const MAPPING_INFO_SIZE: usize = 32;
+const IMAGE_TEXT_INFO_SIZE: usize = 32;
+
+// A range of the shared region backed by a cache file
+#[derive(Debug, Clone, Copy)]
+pub struct CacheMapping {
+    pub address: u64,
+    pub size: u64,
+    pub file_offset: u64,
+}
+
+// The main cache file or one of its subcaches (`.1`, `.01`, ...)
+#[derive(Debug)]
+pub struct CacheFile {
+    pub path: PathBuf,
+    pub uuid: String,
+    pub mappings: Vec<CacheMapping>,
+}
+
+// An image of the cache, from the dyld_cache_image_text_info array
+#[derive(Debug)]
+pub struct CacheImage {
+    pub uuid: String,
+    pub load_address: u64,
+    pub text_size: u32,
+    pub path: String,
+}
+
+// A dyld_shared_cache with its subcaches, indexed by image UUID
+pub struct DyldSharedCache {
+    pub path: PathBuf,
+    pub uuid: String,
+    pub arch: String,
+    // Unslid address of the start of the cache
+    pub base_address: u64,
+    pub files: Vec<CacheFile>,
+    images: HashMap<String, CacheImage>,
+}
+
+impl DyldSharedCache {
+    // Opens the main cache file at `path` together with the subcaches found next
+    // to it. Only headers, mappings and the image list are read.
+    pub fn open(path: &Path) -> io::Result<DyldSharedCache> {
+        let mut file: File = File::open(path)?;
+        let header: Vec<u8> = read_at(&mut file, 0, HEADER_SIZE)?;
+        if !header.starts_with(DYLD_CACHE_MAGIC) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a dyld_shared_cache",
+            ));
+        }
+
+        let arch: String = String::from_utf8_lossy(&header[7..16])
+            .trim_matches(|c: char| c == ' ' || c == '\0')
+            .to_string();
+
+        let main: CacheFile = read_cache_file(&mut file, path, &header)?;
+        let base_address: u64 = main.mappings.first().map_or(0, |m| m.address);
+
+        let mut images: HashMap<String, CacheImage> = HashMap::new();
+        let text_offset: u64 = le_u64(&header, IMAGES_TEXT_OFFSET);
+        let text_count: u64 = le_u64(&header, IMAGES_TEXT_COUNT);
+        if text_offset != 0 && text_count < 0x10000 {
+            let infos: Vec<u8> = read_at(
+                &mut file,
+                text_offset,
+                text_count as usize * IMAGE_TEXT_INFO_SIZE,
+            )?;
+            for info in infos.chunks_exact(IMAGE_TEXT_INFO_SIZE) {
+                let path_offset: u32 = le_u32(info, 28);
+                let image: CacheImage = CacheImage {
+                    uuid: format_uuid(&info[0..16]),
+                    load_address: le_u64(info, 16),
+                    text_size: le_u32(info, 24),
+                    path: read_c_string(&mut file, path_offset as u64)?,
+                };
+                images.insert(image.uuid.clone(), image);
+            }
+        }
+
+        let mut files: Vec<CacheFile> = vec![main];
+        for subcache_path in subcache_paths(path) {
+            let mut subcache: File = File::open(&subcache_path)?;
+            let header: Vec<u8> = read_at(&mut subcache, 0, HEADER_SIZE)?;
+            if header.starts_with(DYLD_CACHE_MAGIC) {
+                files.push(read_cache_file(&mut subcache, &subcache_path, &header)?);
+            }
+        }
+
+        Ok(DyldSharedCache {
+            path: path.to_path_buf(),
+            uuid: files[0].uuid.clone(),
+            arch,
+            base_address,
+            files,
+            images,
+        })
+    }
+
+    pub fn image(&self, uuid: &str) -> Option<&CacheImage> {
+        self.images.get(&uuid.to_lowercase())
+    }
+
+    // The cache file backing an unslid address
+    pub fn file_for_address(&self, address: u64) -> Option<&CacheFile> {
+        self.files.iter().find(|file| {
+            file.mappings
+                .iter()
+                .any(|m| address >= m.address && address < m.address + m.size)
+        })
+    }
+}
+
+fn read_cache_file(file: &mut File, path: &Path, header: &[u8]) -> io::Result<CacheFile> {
+    let mapping_offset: u32 = le_u32(header, MAPPING_OFFSET);
+    let mapping_count: u32 = le_u32(header, MAPPING_COUNT).min(64);
+
+    let raw: Vec<u8> = read_at(
+        file,
+        mapping_offset as u64,
+        mapping_count as usize * MAPPING_INFO_SIZE,
+    )?;
+    let mappings: Vec<CacheMapping> = raw
+        .chunks_exact(MAPPING_INFO_SIZE)
+        .map(|m| CacheMapping {
+            address: le_u64(m, 0),
+            size: le_u64(m, 8),
+            file_offset: le_u64(m, 16),
+        })
+        .collect();
+
+    Ok(CacheFile {
+        path: path.to_path_buf(),
+        uuid: format_uuid(&header[UUID_OFFSET..UUID_OFFSET + 16]),
+        mappings,
+    })
+}
+
+// Subcaches live next to the main cache, named `<main>.<number>` (`.1` up to
+// iOS 15, `.01` since iOS 16). `.symbols` files hold no code.
+fn subcache_paths(path: &Path) -> Vec<PathBuf> {
+    let (dir, name) = match (path.parent(), path.file_name().and_then(|n| n.to_str())) {
+        (Some(dir), Some(name)) => (dir, name),
+        _ => return Vec::new(),
+    };
+    let dir: &Path = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let prefix: String = format!("{}.", name);
+
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_prefix(prefix.as_str()))
+                    .is_some_and(|suffix| {
+                        !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit())
+                    })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+
+    paths
+}
+
+fn read_at(file: &mut File, offset: u64, size: usize) -> io::Result<Vec<u8>> {
+    let mut buffer: Vec<u8> = vec![0; size];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+fn read_c_string(file: &mut File, offset: u64) -> io::Result<String> {
+    let mut buffer: Vec<u8> = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(1024).read_to_end(&mut buffer)?;
+
+    let end: usize = buffer.iter().position(|b| *b == 0).unwrap_or(buffer.len());
+    Ok(String::from_utf8_lossy(&buffer[..end]).to_string())
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn le_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
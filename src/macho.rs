@@ -0,0 +1,567 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const FAT_MAGIC: u32 = 0xcafebabe;
+const FAT_MAGIC_64: u32 = 0xcafebabf;
+
+const LC_UUID: u32 = 0x1b;
+const LC_SEGMENT_64: u32 = 0x19;
+
+// File type of the debug companion files found in .dSYM bundles
+pub const MH_DSYM: u32 = 0xa;
+
+const CPU_TYPE_X86: i32 = 7;
+const CPU_TYPE_ARM: i32 = 12;
+const CPU_ARCH_ABI64: i32 = 0x0100_0000;
+const CPU_ARCH_ABI64_32: i32 = 0x0200_0000;
+
+// A single architecture of a Mach-O file. Thin files have exactly one slice
+// starting at offset 0, fat files have one per architecture.
+#[derive(Debug, Clone)]
+pub struct MachOSlice {
+    pub arch: String,
+    pub uuid: Option<String>,
+    pub filetype: u32,
+    // vmaddr of the __TEXT segment, the address the image offsets are relative to
+    pub text_vmaddr: Option<u64>,
+    pub offset: u64,
+    pub size: u64,
+}
+
+// Reads the slices of a (thin or fat) Mach-O file, looking only at headers and
+// load commands. Returns an empty list for files that are not Mach-O.
+pub fn read_slices(path: &Path) -> io::Result<Vec<MachOSlice>> {
+    let mut file: File = File::open(path)?;
+    let file_size: u64 = file.metadata()?.len();
+
+    let mut magic: [u8; 4] = [0; 4];
+    if file.read_exact(&mut magic).is_err() {
+        return Ok(Vec::new());
+    }
+
+    match u32::from_be_bytes(magic) {
+        FAT_MAGIC | FAT_MAGIC_64 => {
+            read_fat_slices(&mut file, u32::from_be_bytes(magic) == FAT_MAGIC_64)
+        }
+        _ => match read_slice(&mut file, 0, file_size)? {
+            Some(slice) => Ok(vec![slice]),
+            None => Ok(Vec::new()),
+        },
+    }
+}
+
+fn read_fat_slices(file: &mut File, is_64: bool) -> io::Result<Vec<MachOSlice>> {
+    let nfat_arch: u32 = read_u32_be(file)?;
+    // Java class files share the fat magic, they have a much bigger second word
+    if nfat_arch == 0 || nfat_arch > 32 {
+        return Ok(Vec::new());
+    }
+
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for _ in 0..nfat_arch {
+        let _cpu_type: u32 = read_u32_be(file)?;
+        let _cpu_subtype: u32 = read_u32_be(file)?;
+        if is_64 {
+            let offset: u64 = read_u64_be(file)?;
+            let size: u64 = read_u64_be(file)?;
+            let _align: u32 = read_u32_be(file)?;
+            let _reserved: u32 = read_u32_be(file)?;
+            ranges.push((offset, size));
+        } else {
+            let offset: u32 = read_u32_be(file)?;
+            let size: u32 = read_u32_be(file)?;
+            let _align: u32 = read_u32_be(file)?;
+            ranges.push((offset as u64, size as u64));
+        }
+    }
+
+    let mut slices: Vec<MachOSlice> = Vec::new();
+    for (offset, size) in ranges {
+        if let Some(slice) = read_slice(file, offset, size)? {
+            slices.push(slice);
+        }
+    }
+
+    Ok(slices)
+}
+
+fn read_slice(file: &mut File, offset: u64, size: u64) -> io::Result<Option<MachOSlice>> {
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut header: [u8; 32] = [0; 32];
+    if file.read_exact(&mut header[..28]).is_err() {
+        return Ok(None);
+    }
+
+    let header_size: usize = match u32::from_le_bytes(header[0..4].try_into().unwrap()) {
+        MH_MAGIC_64 => 32,
+        MH_MAGIC => 28,
+        _ => return Ok(None),
+    };
+
+    let cpu_type: i32 = i32::from_le_bytes(header[4..8].try_into().unwrap());
+    let cpu_subtype: i32 = i32::from_le_bytes(header[8..12].try_into().unwrap());
+    let filetype: u32 = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    let sizeofcmds: u32 = u32::from_le_bytes(header[20..24].try_into().unwrap());
+
+    file.seek(SeekFrom::Start(offset + header_size as u64))?;
+    let mut commands: Vec<u8> = vec![0; (sizeofcmds as u64).min(size) as usize];
+    file.read_exact(&mut commands)?;
+
+    Ok(Some(MachOSlice {
+        arch: arch_name(cpu_type, cpu_subtype),
+        uuid: find_uuid(&commands),
+        filetype,
+        text_vmaddr: find_text_vmaddr(&commands),
+        offset,
+        size,
+    }))
+}
+
+fn find_uuid(commands: &[u8]) -> Option<String> {
+    let mut position: usize = 0;
+
+    while position + 8 <= commands.len() {
+        let cmd: u32 = u32::from_le_bytes(commands[position..position + 4].try_into().unwrap());
+        let cmdsize: usize =
+            u32::from_le_bytes(commands[position + 4..position + 8].try_into().unwrap()) as usize;
+
+        if cmd == LC_UUID && position + 24 <= commands.len() {
+            return Some(format_uuid(&commands[position + 8..position + 24]));
+        }
+        if cmdsize == 0 {
+            break;
+        }
+        position += cmdsize;
+    }
+
+    None
+}
+
+fn find_text_vmaddr(commands: &[u8]) -> Option<u64> {
+    let mut position: usize = 0;
+
+    while position + 8 <= commands.len() {
+        let cmd: u32 = u32::from_le_bytes(commands[position..position + 4].try_into().unwrap());
+        let cmdsize: usize =
+            u32::from_le_bytes(commands[position + 4..position + 8].try_into().unwrap()) as usize;
+
+        // segment_command_64: cmd, cmdsize, segname[16], vmaddr, ...
+        if cmd == LC_SEGMENT_64
+            && position + 32 <= commands.len()
+            && commands[position + 8..position + 24].starts_with(b"__TEXT\0")
+        {
+            return Some(u64::from_le_bytes(
+                commands[position + 24..position + 32].try_into().unwrap(),
+            ));
+        }
+        if cmdsize == 0 {
+            break;
+        }
+        position += cmdsize;
+    }
+
+    None
+}
+
+// Formats raw UUID bytes like crash reports do: "26e65a28-0861-3213-8bd0-9239f36585a7"
+pub fn format_uuid(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+pub fn arch_name(cpu_type: i32, cpu_subtype: i32) -> String {
+    let subtype: i32 = cpu_subtype & 0xff;
+
+    match cpu_type {
+        t if t == CPU_TYPE_ARM | CPU_ARCH_ABI64 => match subtype {
+            2 => "arm64e",
+            _ => "arm64",
+        },
+        t if t == CPU_TYPE_ARM | CPU_ARCH_ABI64_32 => "arm64_32",
+        t if t == CPU_TYPE_X86 | CPU_ARCH_ABI64 => match subtype {
+            8 => "x86_64h",
+            _ => "x86_64",
+        },
+        CPU_TYPE_ARM => match subtype {
+            11 => "armv7s",
+            12 => "armv7k",
+            _ => "armv7",
+        },
+        CPU_TYPE_X86 => "i386",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn read_u32_be(file: &mut File) -> io::Result<u32> {
+    let mut buffer: [u8; 4] = [0; 4];
+    file.read_exact(&mut buffer)?;
+
+    Ok(u32::from_be_bytes(buffer))
+}
+
+fn read_u64_be(file: &mut File) -> io::Result<u64> {
+    let mut buffer: [u8; 8] = [0; 8];
+    file.read_exact(&mut buffer)?;
+
+    Ok(u64::from_be_bytes(buffer))
+}
+
+const LC_SYMTAB: u32 = 0x2;
+const LC_FUNCTION_STARTS: u32 = 0x26;
+const LC_DYLD_CHAINED_FIXUPS: u32 = 0x8000_0034;
+
+// dyld_chained_fixups_header imports_format values
+const DYLD_CHAINED_IMPORT: u32 = 1;
+const DYLD_CHAINED_IMPORT_ADDEND: u32 = 2;
+const DYLD_CHAINED_IMPORT_ADDEND64: u32 = 3;
+
+const N_STAB: u8 = 0xe0;
+const N_TYPE: u8 = 0x0e;
+const N_SECT: u8 = 0x0e;
+
+// A segment of a 64-bit Mach-O image
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub name: String,
+    pub vmaddr: u64,
+    pub fileoff: u64,
+    pub filesize: u64,
+}
+
+// A section of a 64-bit Mach-O image
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+}
+
+// The content of a 64-bit Mach-O slice, for the lookups that need more than
+// the load command headers (symbols, function starts, ObjC metadata)
+pub struct MachOImage {
+    data: Vec<u8>,
+    pub segments: Vec<Segment>,
+    pub sections: Vec<Section>,
+    symtab: Option<(u32, u32, u32, u32)>,
+    function_starts: Option<(u32, u32)>,
+    chained_fixups: Option<(u32, u32)>,
+}
+
+impl MachOImage {
+    // Reads a whole slice of a Mach-O file
+    pub fn load(path: &Path, slice: &MachOSlice) -> io::Result<MachOImage> {
+        let mut file: File = File::open(path)?;
+        file.seek(SeekFrom::Start(slice.offset))?;
+        let mut data: Vec<u8> = Vec::new();
+        file.take(slice.size).read_to_end(&mut data)?;
+
+        if data.len() < 32 || read_u32_le(&data, 0) != Some(MH_MAGIC_64) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a 64-bit Mach-O",
+            ));
+        }
+
+        let ncmds: u32 = read_u32_le(&data, 16).unwrap_or(0);
+        let mut image: MachOImage = MachOImage {
+            data,
+            segments: Vec::new(),
+            sections: Vec::new(),
+            symtab: None,
+            function_starts: None,
+            chained_fixups: None,
+        };
+
+        let mut position: usize = 32;
+        for _ in 0..ncmds {
+            let (cmd, cmdsize) = match (
+                read_u32_le(&image.data, position),
+                read_u32_le(&image.data, position + 4),
+            ) {
+                (Some(cmd), Some(cmdsize)) if cmdsize >= 8 => (cmd, cmdsize as usize),
+                _ => break,
+            };
+
+            match cmd {
+                LC_SEGMENT_64 => image.read_segment(position),
+                LC_SYMTAB => {
+                    image.symtab = Some((
+                        read_u32_le(&image.data, position + 8).unwrap_or(0),
+                        read_u32_le(&image.data, position + 12).unwrap_or(0),
+                        read_u32_le(&image.data, position + 16).unwrap_or(0),
+                        read_u32_le(&image.data, position + 20).unwrap_or(0),
+                    ))
+                }
+                LC_FUNCTION_STARTS => {
+                    image.function_starts = Some((
+                        read_u32_le(&image.data, position + 8).unwrap_or(0),
+                        read_u32_le(&image.data, position + 12).unwrap_or(0),
+                    ))
+                }
+                LC_DYLD_CHAINED_FIXUPS => {
+                    image.chained_fixups = Some((
+                        read_u32_le(&image.data, position + 8).unwrap_or(0),
+                        read_u32_le(&image.data, position + 12).unwrap_or(0),
+                    ))
+                }
+                _ => {}
+            }
+            position += cmdsize;
+        }
+
+        Ok(image)
+    }
+
+    fn read_segment(&mut self, position: usize) {
+        let data: &[u8] = &self.data;
+        let segment: Segment = Segment {
+            name: read_name(data, position + 8),
+            vmaddr: read_u64_le(data, position + 24).unwrap_or(0),
+            fileoff: read_u64_le(data, position + 40).unwrap_or(0),
+            filesize: read_u64_le(data, position + 48).unwrap_or(0),
+        };
+
+        // section_64 headers follow the segment_command_64 (72 bytes), 80 bytes each
+        let nsects: u32 = read_u32_le(data, position + 64).unwrap_or(0);
+        for n in 0..nsects as usize {
+            let section: usize = position + 72 + n * 80;
+            self.sections.push(Section {
+                name: read_name(data, section),
+                addr: read_u64_le(data, section + 32).unwrap_or(0),
+                size: read_u64_le(data, section + 40).unwrap_or(0),
+            });
+        }
+        self.segments.push(segment);
+    }
+
+    pub fn text_vmaddr(&self) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|s| s.name == "__TEXT")
+            .map(|s| s.vmaddr)
+    }
+
+    // First section with the given name, in any segment (ObjC sections move
+    // between __DATA, __DATA_CONST and __AUTH_CONST)
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    // Bytes at a virtual address, if they are backed by the file
+    pub fn read(&self, address: u64, size: usize) -> Option<&[u8]> {
+        let segment: &Segment = self
+            .segments
+            .iter()
+            .find(|s| address >= s.vmaddr && address + size as u64 <= s.vmaddr + s.filesize)?;
+        let offset: usize = (segment.fileoff + address - segment.vmaddr) as usize;
+
+        self.data.get(offset..offset + size)
+    }
+
+    pub fn read_u32(&self, address: u64) -> Option<u32> {
+        read_u32_le(self.read(address, 4)?, 0)
+    }
+
+    pub fn read_u64(&self, address: u64) -> Option<u64> {
+        read_u64_le(self.read(address, 8)?, 0)
+    }
+
+    // NUL-terminated string at a virtual address
+    pub fn read_c_string(&self, address: u64) -> Option<String> {
+        let segment: &Segment = self
+            .segments
+            .iter()
+            .find(|s| address >= s.vmaddr && address < s.vmaddr + s.filesize)?;
+        let start: usize = (segment.fileoff + address - segment.vmaddr) as usize;
+        let end: usize = (segment.fileoff + segment.filesize) as usize;
+        let bytes: &[u8] = self.data.get(start..end.min(self.data.len()))?;
+        let length: usize = bytes.iter().position(|b| *b == 0)?;
+
+        Some(String::from_utf8_lossy(&bytes[..length]).to_string())
+    }
+
+    // Data of the LC_DYLD_CHAINED_FIXUPS command (dyld_chained_fixups_header)
+    fn chained_fixups_data(&self) -> Option<&[u8]> {
+        let (dataoff, datasize) = self.chained_fixups?;
+
+        self.data
+            .get(dataoff as usize..dataoff as usize + datasize as usize)
+    }
+
+    // Pointer format of the chained fixups (DYLD_CHAINED_PTR_*), taken from the
+    // first segment that has fixups. None for binaries using the older dyld info.
+    pub fn chained_pointer_format(&self) -> Option<u16> {
+        let fixups: &[u8] = self.chained_fixups_data()?;
+        // dyld_chained_starts_in_image: seg_count, seg_info_offset[seg_count]
+        let starts: usize = read_u32_le(fixups, 4)? as usize;
+        let seg_count: u32 = read_u32_le(fixups, starts)?;
+
+        (0..seg_count as usize).find_map(|n| {
+            let seg_info_offset: usize = read_u32_le(fixups, starts + 4 + n * 4)? as usize;
+            if seg_info_offset == 0 {
+                return None;
+            }
+            // dyld_chained_starts_in_segment: size, page_size (u16), pointer_format (u16)
+            let format: &[u8] = fixups.get(starts + seg_info_offset + 6..starts + seg_info_offset + 8)?;
+
+            Some(u16::from_le_bytes([format[0], format[1]]))
+        })
+    }
+
+    // Names of the symbols imported through chained fixups, by bind ordinal
+    pub fn chained_imports(&self) -> Vec<String> {
+        let fixups: &[u8] = match self.chained_fixups_data() {
+            Some(fixups) => fixups,
+            None => return Vec::new(),
+        };
+        let (imports, symbols, count, format) = match (
+            read_u32_le(fixups, 8),
+            read_u32_le(fixups, 12),
+            read_u32_le(fixups, 16),
+            read_u32_le(fixups, 20),
+        ) {
+            (Some(imports), Some(symbols), Some(count), Some(format)) => {
+                (imports as usize, symbols as usize, count as usize, format)
+            }
+            _ => return Vec::new(),
+        };
+
+        (0..count)
+            .map_while(|n| {
+                let name_offset: u64 = match format {
+                    // lib_ordinal:8, weak_import:1, name_offset:23
+                    DYLD_CHAINED_IMPORT => (read_u32_le(fixups, imports + n * 4)? >> 9) as u64,
+                    DYLD_CHAINED_IMPORT_ADDEND => (read_u32_le(fixups, imports + n * 8)? >> 9) as u64,
+                    // lib_ordinal:16, weak_import:1, reserved:15, name_offset:32
+                    DYLD_CHAINED_IMPORT_ADDEND64 => read_u64_le(fixups, imports + n * 16)? >> 32,
+                    _ => return None,
+                };
+                let name: &[u8] = fixups.get(symbols + name_offset as usize..)?;
+                let name: &[u8] = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+
+                Some(String::from_utf8_lossy(name).to_string())
+            })
+            .collect()
+    }
+
+    // Defined symbols of the symbol table (no debugging entries), with their
+    // unslid address
+    pub fn symbols(&self) -> Vec<(u64, String)> {
+        let (symoff, nsyms, stroff, strsize) = match self.symtab {
+            Some(symtab) => symtab,
+            None => return Vec::new(),
+        };
+        let strings: &[u8] = match self
+            .data
+            .get(stroff as usize..(stroff as usize + strsize as usize))
+        {
+            Some(strings) => strings,
+            None => return Vec::new(),
+        };
+
+        let mut symbols: Vec<(u64, String)> = Vec::new();
+        for n in 0..nsyms as usize {
+            // nlist_64: n_strx u32, n_type u8, n_sect u8, n_desc u16, n_value u64
+            let entry: &[u8] = match self
+                .data
+                .get(symoff as usize + n * 16..symoff as usize + n * 16 + 16)
+            {
+                Some(entry) => entry,
+                None => break,
+            };
+            let n_type: u8 = entry[4];
+            if n_type & N_STAB != 0 || n_type & N_TYPE != N_SECT {
+                continue;
+            }
+
+            let n_strx: usize = read_u32_le(entry, 0).unwrap_or(0) as usize;
+            let name: &[u8] = match strings.get(n_strx..) {
+                Some(name) => &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())],
+                None => continue,
+            };
+            if name.is_empty() {
+                continue;
+            }
+
+            symbols.push((
+                read_u64_le(entry, 8).unwrap_or(0),
+                String::from_utf8_lossy(name).to_string(),
+            ));
+        }
+
+        symbols
+    }
+
+    // Start addresses of the functions, from the ULEB128 deltas of LC_FUNCTION_STARTS
+    pub fn function_starts(&self) -> Vec<u64> {
+        let (dataoff, datasize) = match self.function_starts {
+            Some(function_starts) => function_starts,
+            None => return Vec::new(),
+        };
+        let data: &[u8] = match self
+            .data
+            .get(dataoff as usize..(dataoff as usize + datasize as usize))
+        {
+            Some(data) => data,
+            None => return Vec::new(),
+        };
+
+        let mut starts: Vec<u64> = Vec::new();
+        let mut address: u64 = self.text_vmaddr().unwrap_or(0);
+        let mut position: usize = 0;
+        while position < data.len() {
+            let mut delta: u64 = 0;
+            let mut shift: u32 = 0;
+            while position < data.len() {
+                let byte: u8 = data[position];
+                position += 1;
+                if shift < 64 {
+                    delta |= ((byte & 0x7f) as u64) << shift;
+                }
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            if delta == 0 {
+                break;
+            }
+            address += delta;
+            starts.push(address);
+        }
+
+        starts
+    }
+}
+
+fn read_name(data: &[u8], offset: usize) -> String {
+    let bytes: &[u8] = data.get(offset..offset + 16).unwrap_or_default();
+    let length: usize = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+
+    String::from_utf8_lossy(&bytes[..length]).to_string()
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64_le(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
@@ -4,25 +4,53 @@ use std::io::Write;
 use std::path::Path;
 use std::process;
 
-#[macro_use]
-extern crate r2pipe;
-
+mod annotate;
+mod app_bundle;
+mod arm64;
+mod binary_locator;
+mod byte_stream;
+mod cfg;
+mod config;
 mod crash_log_analyzer;
+mod decoders;
+mod decompiler;
+mod demangle;
+mod dwarf;
+mod dyld_cache;
+mod macho;
+mod parsers;
+mod pointer_auth;
+mod provenance;
 mod r2pipe_cache;
+mod objc;
 mod structs;
+mod symbol_store;
+mod symbolicator;
 
+use config::Config;
 use crash_log_analyzer::CrashLogAnalyzer;
 
 pub const OUTPUT_FOLDER: &str = "output";
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <ips_file_path>", args[0]);
-        process::exit(1);
-    }
+    let config: Config = match Config::from_args(&args[1..]) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            println!(
+                "Usage: {} [-a|--app <ipa|xcarchive|app>]... [-s|--search-path <dir>]... \
+                 [-S|--symbol-store <dir>]... \
+                 [-d|--dyld-cache <file>]... [--source] [--mangled] [-w|--window <n>] [--provenance] \
+                 [--decompile] [--decompiler <auto|pdg|pdd|pdc>] [--cfg] \
+                 [-t|--threads <all|i,j,...>] [--disassemble-threads <all|i,j,...>] [-c|--config <file>] <crash_report_path>",
+                args[0]
+            );
+            process::exit(1);
+        }
+    };
 
-    let ips_file: &String = &args[1];
+    let ips_file: &String = &config.report_path;
     let path: &Path = Path::new(ips_file);
     if !path.exists() {
         println!("{} does not exist.", ips_file);
@@ -36,7 +64,15 @@ fn main() -> std::io::Result<()> {
         return Ok(());
     }
 
-    let mut analyzer: CrashLogAnalyzer = CrashLogAnalyzer::new(ips_data);
+    let mut analyzer: CrashLogAnalyzer = match CrashLogAnalyzer::new(ips_data, &config) {
+        Ok(analyzer) => analyzer,
+        Err(e) => {
+            println!("Unable to parse {}: {}", ips_file, e);
+            process::exit(1);
+        }
+    };
+
+    analyzer.symbolicate();
 
     let filename: &str = path.file_stem().unwrap().to_str().unwrap();
     let mut file: File = File::create(format!("{OUTPUT_FOLDER}/{filename}"))?;
@@ -47,8 +83,10 @@ fn main() -> std::io::Result<()> {
     let _ = file.write_all(exception_info.as_bytes());
     let registers: String = analyzer.parse_registers();
     let _ = file.write_all(registers.as_bytes());
+    let instruction_bytes: String = analyzer.parse_instruction_bytes();
+    let _ = file.write_all(instruction_bytes.as_bytes());
     let stacktrace: String = analyzer.analyze_faulting_thread();
-    let _ = file.write_all(stacktrace.as_bytes()).unwrap();
+    file.write_all(stacktrace.as_bytes()).unwrap();
 
     Ok(())
 }
@@ -0,0 +1,297 @@
+use crate::macho::MachOImage;
+
+// class_rw_t/class_ro_t pointers of Swift and ObjC classes carry flags in
+// their low bits
+const FAST_DATA_MASK: u64 = 0x0000_7fff_ffff_fff8;
+// method_list_t flag of lists made of 32-bit relative offsets
+const RELATIVE_METHODS_FLAG: u32 = 0x8000_0000;
+const METHOD_LIST_FLAGS_MASK: u32 = 0xffff_0003;
+
+// Chained fixup pointer formats (DYLD_CHAINED_PTR_*)
+const DYLD_CHAINED_PTR_64: u16 = 2;
+const DYLD_CHAINED_PTR_64_OFFSET: u16 = 6;
+const DYLD_CHAINED_PTR_ARM64E_USERLAND24: u16 = 12;
+
+// Symbols of the classes other images define, referenced by categories
+const OBJC_CLASS_SYMBOL_PREFIX: &str = "_OBJC_CLASS_$_";
+
+// An ObjC class found in __objc_classlist
+struct ObjcClass {
+    name: String,
+    // class_ro_t of the class and of its metaclass
+    ro: u64,
+    meta_ro: Option<u64>,
+}
+
+// A method of a class or of a category, from the ObjC runtime metadata
+pub struct ObjcMethod {
+    pub class: String,
+    pub category: Option<String>,
+    pub selector: String,
+    pub is_class_method: bool,
+    // Unslid address of the implementation
+    pub imp: u64,
+}
+
+impl ObjcMethod {
+    // Named like the symbols the compiler emits: "-[Class selector]",
+    // "+[Class(Category) selector]"
+    pub fn name(&self) -> String {
+        let prefix: char = if self.is_class_method { '+' } else { '-' };
+
+        match &self.category {
+            Some(category) => format!("{}[{}({}) {}]", prefix, self.class, category, self.selector),
+            None => format!("{}[{} {}]", prefix, self.class, self.selector),
+        }
+    }
+}
+
+// A pointer stored in the __DATA segments of binaries using chained fixups
+enum ChainedPointer {
+    // Unslid address
+    Rebase(u64),
+    // Index in the imports of the chained fixups
+    Bind(u32),
+}
+
+// Methods of the classes (__objc_classlist) and categories (__objc_catlist)
+// of an image. Stripped binaries keep this metadata, the runtime needs it.
+pub fn methods(image: &MachOImage) -> Vec<ObjcMethod> {
+    let mut methods: Vec<ObjcMethod> = Vec::new();
+
+    for class in classes(image) {
+        for (is_class_method, ro) in [(false, Some(class.ro)), (true, class.meta_ro)] {
+            let ro: u64 = match ro {
+                Some(ro) => ro,
+                None => continue,
+            };
+            // class_ro_t: flags, instanceStart, instanceSize, reserved,
+            // ivarLayout, name, baseMethods, ...
+            let method_list: u64 = match image.read_u64(ro + 32).map(|p| decode_pointer(image, p)) {
+                Some(method_list) if method_list != 0 => method_list,
+                _ => continue,
+            };
+
+            for (selector, imp) in read_method_list(image, method_list) {
+                methods.push(ObjcMethod {
+                    class: class.name.clone(),
+                    category: None,
+                    selector,
+                    is_class_method,
+                    imp,
+                });
+            }
+        }
+    }
+    methods.extend(category_methods(image));
+
+    methods
+}
+
+// Unslid addresses of the implementations of a selector in a class. Without a
+// category, the methods of the class and of all its categories are candidates.
+pub fn implementations(
+    methods: &[ObjcMethod],
+    class: &str,
+    category: Option<&str>,
+    selector: &str,
+    is_class_method: bool,
+) -> Vec<u64> {
+    methods
+        .iter()
+        .filter(|m| {
+            m.class == class
+                && m.selector == selector
+                && m.is_class_method == is_class_method
+                && category.is_none_or(|category| m.category.as_deref() == Some(category))
+        })
+        .map(|m| m.imp)
+        .collect()
+}
+
+fn classes(image: &MachOImage) -> Vec<ObjcClass> {
+    let section = match image.section("__objc_classlist") {
+        Some(section) => section,
+        None => return Vec::new(),
+    };
+
+    (0..section.size / 8)
+        .filter_map(|n| {
+            let class: u64 = decode_pointer(image, image.read_u64(section.addr + n * 8)?);
+            let ro: u64 = class_ro(image, class)?;
+            let name: String = class_name(image, ro)?;
+
+            let metaclass: Option<u64> = image
+                .read_u64(class)
+                .map(|isa| decode_pointer(image, isa))
+                .filter(|isa| *isa != 0);
+            let meta_ro: Option<u64> = metaclass.and_then(|meta| class_ro(image, meta));
+
+            Some(ObjcClass { name, ro, meta_ro })
+        })
+        .collect()
+}
+
+// Methods of the categories of __objc_catlist. The class they extend is either
+// defined in the image or imported (a bind to "_OBJC_CLASS_$_<name>"); binaries
+// without chained fixups leave imported classes unnamed on disk, their
+// categories are skipped.
+fn category_methods(image: &MachOImage) -> Vec<ObjcMethod> {
+    let section = match image.section("__objc_catlist") {
+        Some(section) => section,
+        None => return Vec::new(),
+    };
+    let imports: Vec<String> = image.chained_imports();
+    let mut methods: Vec<ObjcMethod> = Vec::new();
+
+    for n in 0..section.size / 8 {
+        // category_t: name, cls, instanceMethods, classMethods, protocols, ...
+        let category: u64 = match image.read_u64(section.addr + n * 8) {
+            Some(pointer) => decode_pointer(image, pointer),
+            None => continue,
+        };
+        let category_name: Option<String> = image
+            .read_u64(category)
+            .and_then(|p| image.read_c_string(decode_pointer(image, p)));
+        let class_name: Option<String> = match image.read_u64(category + 8).map(|p| chained_pointer(image, p)) {
+            Some(ChainedPointer::Rebase(class)) if class != 0 => {
+                class_ro(image, class).and_then(|ro| class_name(image, ro))
+            }
+            Some(ChainedPointer::Bind(ordinal)) => imports
+                .get(ordinal as usize)
+                .and_then(|symbol| symbol.strip_prefix(OBJC_CLASS_SYMBOL_PREFIX))
+                .map(|name| name.to_string()),
+            _ => None,
+        };
+        let (category_name, class_name) = match (category_name, class_name) {
+            (Some(category_name), Some(class_name)) => (category_name, class_name),
+            _ => continue,
+        };
+
+        for (is_class_method, field) in [(false, 16), (true, 24)] {
+            let method_list: u64 = match image.read_u64(category + field).map(|p| decode_pointer(image, p)) {
+                Some(method_list) if method_list != 0 => method_list,
+                _ => continue,
+            };
+
+            for (selector, imp) in read_method_list(image, method_list) {
+                methods.push(ObjcMethod {
+                    class: class_name.clone(),
+                    category: Some(category_name.clone()),
+                    selector,
+                    is_class_method,
+                    imp,
+                });
+            }
+        }
+    }
+
+    methods
+}
+
+// class_t: isa, superclass, cache, vtable, data (class_ro_t on disk)
+fn class_ro(image: &MachOImage, class: u64) -> Option<u64> {
+    let data: u64 = decode_pointer(image, image.read_u64(class + 32)?);
+
+    Some(data & FAST_DATA_MASK).filter(|ro| *ro != 0)
+}
+
+fn class_name(image: &MachOImage, ro: u64) -> Option<String> {
+    let name_pointer: u64 = decode_pointer(image, image.read_u64(ro + 24)?);
+
+    image.read_c_string(name_pointer)
+}
+
+// Reads a method_list_t, either made of absolute pointers (name, types, imp)
+// or of 32-bit offsets relative to each field (selector reference, types, imp)
+fn read_method_list(image: &MachOImage, list: u64) -> Vec<(String, u64)> {
+    let (entsize_and_flags, count) = match (image.read_u32(list), image.read_u32(list + 4)) {
+        (Some(entsize_and_flags), Some(count)) => (entsize_and_flags, count),
+        _ => return Vec::new(),
+    };
+    let is_relative: bool = entsize_and_flags & RELATIVE_METHODS_FLAG != 0;
+    let entsize: u64 = (entsize_and_flags & !METHOD_LIST_FLAGS_MASK) as u64;
+    if entsize == 0 || count > 0x10000 {
+        return Vec::new();
+    }
+
+    (0..count as u64)
+        .filter_map(|n| {
+            let method: u64 = list + 8 + n * entsize;
+            if is_relative {
+                let name_offset: i32 = image.read_u32(method)? as i32;
+                let imp_offset: i32 = image.read_u32(method + 8)? as i32;
+                let selector_ref: u64 = method.wrapping_add_signed(name_offset as i64);
+                let selector: u64 = decode_pointer(image, image.read_u64(selector_ref)?);
+                let imp: u64 = (method + 8).wrapping_add_signed(imp_offset as i64);
+
+                Some((image.read_c_string(selector)?, imp))
+            } else {
+                let selector: u64 = decode_pointer(image, image.read_u64(method)?);
+                let imp: u64 = decode_pointer(image, image.read_u64(method + 16)?);
+
+                Some((image.read_c_string(selector)?, imp))
+            }
+        })
+        .filter(|(_, imp)| *imp != 0)
+        .collect()
+}
+
+// Pointers stored in the __DATA segments of binaries using chained fixups are
+// rebase entries rather than addresses: the target is either an unslid address
+// or an offset from the start of the image, in the low bits. Binds (pointers to
+// other images) resolve to 0.
+pub fn decode_pointer(image: &MachOImage, raw: u64) -> u64 {
+    match chained_pointer(image, raw) {
+        ChainedPointer::Rebase(address) => address,
+        ChainedPointer::Bind(_) => 0,
+    }
+}
+
+// Decodes a pointer with the layout of the pointer format of the image, or
+// with the arm64e layout when the image does not say
+fn chained_pointer(image: &MachOImage, raw: u64) -> ChainedPointer {
+    let base: u64 = image.text_vmaddr().unwrap_or(0);
+    let rebase = |target: u64| {
+        ChainedPointer::Rebase(if target != 0 && target < base {
+            base + target
+        } else {
+            target
+        })
+    };
+
+    if raw >> 48 == 0 {
+        // Plain pointer or rebase without high bits
+        return rebase(raw);
+    }
+
+    match image.chained_pointer_format() {
+        Some(DYLD_CHAINED_PTR_64 | DYLD_CHAINED_PTR_64_OFFSET) => {
+            // bind:1 (bit 63); binds hold a 24-bit ordinal, rebases a 36-bit target
+            if raw >> 63 == 1 {
+                ChainedPointer::Bind((raw & 0xff_ffff) as u32)
+            } else {
+                rebase(raw & 0xf_ffff_ffff)
+            }
+        }
+        format => {
+            // auth:1 (bit 63), bind:1 (bit 62)
+            let is_auth: bool = raw >> 63 == 1;
+            let is_bind: bool = (raw >> 62) & 1 == 1;
+            if is_bind {
+                let ordinal_mask: u64 = if format == Some(DYLD_CHAINED_PTR_ARM64E_USERLAND24) {
+                    0xff_ffff
+                } else {
+                    0xffff
+                };
+                return ChainedPointer::Bind((raw & ordinal_mask) as u32);
+            }
+
+            rebase(if is_auth {
+                raw & 0xffff_ffff
+            } else {
+                raw & 0x7ff_ffff_ffff
+            })
+        }
+    }
+}
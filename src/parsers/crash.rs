@@ -0,0 +1,567 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+
+use super::{ParseError, ParsedReport};
+use crate::pointer_auth::PointerAuth;
+use crate::structs::crash_info::*;
+use crate::structs::incident_report::IncidentReport;
+
+lazy_static! {
+    // "Exception Type:  EXC_BAD_ACCESS (SIGSEGV)"
+    static ref HEADER_FIELD: Regex = Regex::new(r"^([A-Za-z][A-Za-z /()-]*?):\s*(.*)$").unwrap();
+    // "Thread 0 name:  Dispatch queue: com.apple.main-thread"
+    static ref THREAD_NAME: Regex = Regex::new(r"^Thread (\d+) name:\s*(.*)$").unwrap();
+    // "Thread 0 Crashed:" (iOS) or "Thread 0 Crashed::  Dispatch queue: ..." (macOS)
+    static ref THREAD_START: Regex = Regex::new(r"^Thread (\d+)( Crashed)?:{1,2}\s*(.*)$").unwrap();
+    // "Thread 0 crashed with ARM Thread State (64-bit):"
+    static ref THREAD_STATE_START: Regex = Regex::new(r"^Thread (\d+) crashed with (.*) Thread State").unwrap();
+    // "3   UIKitCore    0x0000000190e2cd38 -[UIApplication sendAction:] + 100"
+    static ref FRAME: Regex = Regex::new(r"^(\d+)\s+(.+?)\s+(0x[0-9a-fA-F]+)\s+(.*)$").unwrap();
+    // "0x104724000 + 156364"
+    static ref UNSYMBOLICATED: Regex = Regex::new(r"^0x[0-9a-fA-F]+ \+ (\d+)$").unwrap();
+    // "Foo.bar() + 123 (File.swift:42) [inlined]"
+    static ref SYMBOLICATED: Regex = Regex::new(r"^(.*) \+ (\d+)(?: \([^()]*\))?(?: \[inlined\])?$").unwrap();
+    // "0x104724000 - 0x1047dffff GlitchChat arm64  <26e65a2808613213...> /path/to/GlitchChat"
+    static ref BINARY_IMAGE: Regex = Regex::new(
+        r"^\s*(0x[0-9a-fA-F]+)\s*-\s*(0x[0-9a-fA-F]+)\s+\+?(.+?)\s+(?:(arm64e|arm64_32|arm64|armv7k|armv7s|armv7|x86_64h|x86_64|i386)\s+)?(?:\([^)]*\)\s+)?<([0-9a-fA-F-]+)>\s*(.*)$"
+    )
+    .unwrap();
+    // "x0: 0x0000000282a50d20"
+    static ref REGISTER: Regex = Regex::new(r"([a-z0-9]+):\s*(0x[0-9a-fA-F]+)").unwrap();
+    // "esr: 0xf2000001 (Breakpoint) brk 1"
+    static ref ESR_DESCRIPTION: Regex = Regex::new(r"esr:\s*0x[0-9a-fA-F]+\s+(.*)$").unwrap();
+    // "GlitchChat [3036]"
+    static ref NAME_AND_PID: Regex = Regex::new(r"^(.*?)\s*\[(\d+)\]$").unwrap();
+    // "iPhone OS 16.3 (20D47)", "1.0 (1)", "EXC_BAD_ACCESS (SIGSEGV)"
+    static ref TRAILING_PARENS: Regex = Regex::new(r"^(.*?)\s*\(([^)]*)\)$").unwrap();
+}
+
+// Parses a legacy plain-text .crash report (Xcode Organizer, older iOS builds)
+// into the same structures used for .ips reports.
+pub fn parse(data: &str) -> Result<ParsedReport, ParseError> {
+    if data.trim().is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    let lines: Vec<&str> = data.lines().map(|l| l.trim_end()).collect();
+
+    // Binary images come last in the file but frames need them to get an image index
+    let used_images: Vec<UsedImage> = parse_binary_images(&lines);
+    let fields: HashMap<&str, &str> = parse_header_fields(&lines);
+
+    let process: &str = fields.get("Process").ok_or(ParseError::MissingField("Process"))?;
+    let (proc_name, _) = split_name_and_pid(process);
+    let proc_path: String = fields.get("Path").unwrap_or(&"").to_string();
+    let (parent_proc, parent_pid) = split_name_and_pid(fields.get("Parent Process").unwrap_or(&""));
+
+    let (app_version, build_version) = match fields.get("Version") {
+        Some(version) => match TRAILING_PARENS.captures(version) {
+            Some(c) => (c[1].to_string(), c[2].to_string()),
+            None => (version.to_string(), String::new()),
+        },
+        None => (String::new(), String::new()),
+    };
+
+    let os_version_line: String = fields
+        .get("OS Version")
+        .ok_or(ParseError::MissingField("OS Version"))?
+        .to_string();
+    let (train, build) = match TRAILING_PARENS.captures(&os_version_line) {
+        Some(c) => (c[1].to_string(), c[2].to_string()),
+        None => (os_version_line.clone(), String::new()),
+    };
+
+    let is_embedded: bool = !(train.starts_with("macOS") || train.starts_with("Mac OS"));
+    let cpu_type: String = fields
+        .get("Code Type")
+        .and_then(|c| c.split_whitespace().next())
+        .unwrap_or_default()
+        .to_string();
+
+    let image_map: ImageMap = ImageMap {
+        images: &used_images,
+        pointer_auth: PointerAuth::new(&cpu_type, is_embedded, &used_images),
+    };
+    let (mut threads, last_exception_backtrace) = parse_threads(&lines, &image_map);
+    if threads.is_empty() {
+        return Err(ParseError::MissingField("Thread 0"));
+    }
+    for thread in threads.iter_mut() {
+        if let Some(ThreadState::X86_64(state)) = thread.thread_state.as_mut() {
+            fill_x86_trap_info(state, &fields);
+        }
+    }
+
+    let faulting_thread: u32 = fields
+        .get("Triggered by Thread")
+        .and_then(|t| t.parse::<u32>().ok())
+        .or_else(|| {
+            threads
+                .iter()
+                .position(|t| t.triggered.is_some())
+                .map(|i| i as u32)
+        })
+        .unwrap_or(0);
+
+    let slice_uuid: String = used_images
+        .iter()
+        .find(|image| image.name.as_deref() == Some(proc_name.as_str()))
+        .map(|image| image.uuid.clone())
+        .unwrap_or_default();
+
+    let is_first_party: u8 =
+        (proc_path.starts_with("/System/") || proc_path.starts_with("/usr/")) as u8;
+
+    let general_info: IncidentReport = IncidentReport {
+        name: proc_name.clone(),
+        app_name: proc_name.clone(),
+        timestamp: fields.get("Date/Time").unwrap_or(&"").to_string(),
+        app_version,
+        slice_uuid,
+        build_version,
+        is_first_party,
+        bug_type: String::from("109"),
+        os_version: os_version_line.clone(),
+        incident_id: fields.get("Incident Identifier").unwrap_or(&"").to_string(),
+        bundle_id: fields.get("Identifier").map(|s| s.to_string()),
+        ..Default::default()
+    };
+
+    let crash_info: CrashInfo = CrashInfo {
+        user_id: fields
+            .get("User ID")
+            .and_then(|u| u.parse::<u32>().ok())
+            .unwrap_or_default(),
+        model_code: fields.get("Hardware Model").unwrap_or(&"").to_string(),
+        os_version: OSVersion {
+            is_embedded,
+            train,
+            release_type: fields.get("Release Type").unwrap_or(&"").to_string(),
+            build,
+        },
+        cpu_type,
+        proc_name,
+        proc_path,
+        parent_proc,
+        parent_pid: parent_pid.unwrap_or_default(),
+        coalition_name: fields.get("Coalition").map(|c| split_name_and_pid(c).0),
+        exception: parse_exception(&fields),
+        faulting_thread,
+        threads,
+        used_images,
+        shared_cache: None,
+        termination: parse_termination(&fields),
+        last_exception_backtrace,
+        // Only .ips reports capture the bytes around pc
+        instruction_byte_stream: None,
+    };
+
+    Ok(ParsedReport {
+        general_info,
+        crash_info,
+    })
+}
+
+// Collects the "Key: value" lines of the report. The first occurrence wins.
+fn parse_header_fields<'l>(lines: &[&'l str]) -> HashMap<&'l str, &'l str> {
+    let mut fields: HashMap<&str, &str> = HashMap::new();
+
+    for line in lines {
+        if line.starts_with("Binary Images") {
+            break;
+        }
+        if let Some(c) = HEADER_FIELD.captures(line) {
+            let key: &str = c.get(1).unwrap().as_str();
+            let value: &str = c.get(2).unwrap().as_str().trim();
+            fields.entry(key).or_insert(value);
+        }
+    }
+
+    fields
+}
+
+fn parse_binary_images(lines: &[&str]) -> Vec<UsedImage> {
+    let mut images: Vec<UsedImage> = Vec::new();
+
+    let start: Option<usize> = lines.iter().position(|l| l.starts_with("Binary Images"));
+    if start.is_none() {
+        return images;
+    }
+
+    for line in &lines[start.unwrap() + 1..] {
+        if line.trim().is_empty() {
+            if images.is_empty() {
+                continue;
+            }
+            break;
+        }
+
+        let Some(c) = BINARY_IMAGE.captures(line) else {
+            continue;
+        };
+
+        let base: u64 = parse_hex(&c[1]).unwrap_or_default();
+        let end: u64 = parse_hex(&c[2]).unwrap_or(base);
+        let path: &str = c.get(6).map(|p| p.as_str().trim()).unwrap_or_default();
+
+        images.push(UsedImage {
+            source: String::from("P"),
+            arch: c.get(4).map(|a| a.as_str().to_string()),
+            base,
+            size: end.saturating_sub(base) + 1,
+            uuid: normalize_uuid(&c[5]),
+            path: if path.is_empty() {
+                None
+            } else {
+                Some(path.to_string())
+            },
+            name: Some(c[3].trim().to_string()),
+        });
+    }
+
+    images
+}
+
+fn parse_threads(lines: &[&str], images: &ImageMap) -> (Vec<Thread>, Option<Vec<Frame>>) {
+    let mut threads: Vec<Thread> = Vec::new();
+    let mut names: HashMap<u32, &str> = HashMap::new();
+    let mut last_exception_backtrace: Option<Vec<Frame>> = None;
+
+    let mut i: usize = 0;
+    while i < lines.len() {
+        let line: &str = lines[i];
+
+        if line.starts_with("Binary Images") {
+            break;
+        }
+
+        if line.starts_with("Last Exception Backtrace") {
+            let (frames, next) = parse_frames(lines, i + 1, images);
+            last_exception_backtrace = Some(frames);
+            i = next;
+            continue;
+        }
+
+        if let Some(c) = THREAD_NAME.captures(line) {
+            names.insert(c[1].parse().unwrap_or_default(), c.get(2).unwrap().as_str());
+            i += 1;
+            continue;
+        }
+
+        if let Some(c) = THREAD_STATE_START.captures(line) {
+            let index: usize = c[1].parse().unwrap_or_default();
+            let (state, next) = parse_thread_state(lines, i + 1, &c[2]);
+            if let Some(thread) = threads.get_mut(index) {
+                thread.thread_state = Some(state);
+            }
+            i = next;
+            continue;
+        }
+
+        if let Some(c) = THREAD_START.captures(line) {
+            let index: u32 = c[1].parse().unwrap_or_default();
+            let label: &str = c.get(3).unwrap().as_str();
+            let (frames, next) = parse_frames(lines, i + 1, images);
+
+            // Legacy reports don't carry thread IDs, the thread number is used instead
+            let mut thread: Thread = Thread {
+                id: index,
+                triggered: c.get(2).map(|_| true),
+                frames,
+                ..Default::default()
+            };
+            let name: &str = names.get(&index).copied().unwrap_or(label);
+            match name.strip_prefix("Dispatch queue:") {
+                Some(queue) => thread.queue = Some(queue.trim().to_string()),
+                None if !name.is_empty() => thread.name = Some(name.trim().to_string()),
+                None => {}
+            }
+
+            threads.push(thread);
+            i = next;
+            continue;
+        }
+
+        i += 1;
+    }
+
+    (threads, last_exception_backtrace)
+}
+
+// Reads stack frame lines starting at `start` until the first line that isn't a frame.
+// Also accepts the old "(0x1a2b 0x1b3c ...)" unsymbolicated backtrace form.
+fn parse_frames(lines: &[&str], start: usize, images: &ImageMap) -> (Vec<Frame>, usize) {
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut i: usize = start;
+
+    while i < lines.len() {
+        let line: &str = lines[i].trim_start();
+
+        if line.starts_with('(') && line.ends_with(')') {
+            line[1..line.len() - 1]
+                .split_whitespace()
+                .filter_map(parse_hex)
+                .for_each(|address| frames.push(images.frame_for_address(address, None)));
+            i += 1;
+            continue;
+        }
+
+        let Some(c) = FRAME.captures(line) else {
+            break;
+        };
+
+        let image_name: &str = c.get(2).unwrap().as_str();
+        let address: u64 = parse_hex(&c[3]).unwrap_or_default();
+        let mut frame: Frame = images.frame_for_address(address, Some(image_name));
+
+        let rest: &str = c.get(4).unwrap().as_str().trim();
+        if let Some(u) = UNSYMBOLICATED.captures(rest) {
+            frame.image_offset = u[1].parse().unwrap_or(frame.image_offset);
+        } else if let Some(s) = SYMBOLICATED.captures(rest) {
+            frame.symbol = Some(s[1].to_string());
+            frame.symbol_location = s[2].parse().ok();
+        } else if !rest.is_empty() {
+            frame.symbol = Some(rest.to_string());
+        }
+
+        frames.push(frame);
+        i += 1;
+    }
+
+    (frames, i)
+}
+
+// Binary images of the report, used to turn absolute addresses into frames
+struct ImageMap<'a> {
+    images: &'a [UsedImage],
+    pointer_auth: Option<PointerAuth>,
+}
+
+impl ImageMap<'_> {
+    // Builds a frame for an absolute address, preferring the image whose range
+    // contains it and falling back to the image name printed in the frame.
+    // Signed arm64e addresses are stripped before the lookup.
+    fn frame_for_address(&self, address: u64, image_name: Option<&str>) -> Frame {
+        let address: u64 = match self.pointer_auth {
+            Some(pointer_auth) => pointer_auth.strip(address),
+            None => address,
+        };
+
+        let index: Option<usize> = self
+            .images
+            .iter()
+            .position(|image| address >= image.base && address < image.base + image.size)
+            .or_else(|| {
+                image_name.and_then(|name| {
+                    self.images
+                        .iter()
+                        .position(|image| image.name.as_deref() == Some(name))
+                })
+            });
+
+        match index {
+            Some(index) => Frame {
+                image_offset: address.saturating_sub(self.images[index].base),
+                image_index: index as u32,
+                ..Default::default()
+            },
+            None => Frame {
+                image_offset: address,
+                image_index: u32::MAX,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// Reads the "Thread N crashed with <arch> Thread State" register block.
+// `arch` is "ARM" or "X86" as printed in the section title.
+fn parse_thread_state(lines: &[&str], start: usize, arch: &str) -> (ThreadState, usize) {
+    let mut registers: HashMap<String, u64> = HashMap::new();
+    let mut esr_description: String = String::new();
+    let mut i: usize = start;
+
+    while i < lines.len() && !lines[i].trim().is_empty() {
+        for c in REGISTER.captures_iter(lines[i]) {
+            if let Some(value) = parse_hex(&c[2]) {
+                registers.insert(c[1].to_string(), value);
+            }
+        }
+        if let Some(c) = ESR_DESCRIPTION.captures(lines[i]) {
+            esr_description = c[1].trim().to_string();
+        }
+        i += 1;
+    }
+
+    let register = |name: &str| Register {
+        value: registers.get(name).copied().unwrap_or_default(),
+        ..Default::default()
+    };
+
+    if arch.starts_with("X86") {
+        let state: X86ThreadState = X86ThreadState {
+            flavor: String::from("x86_THREAD_STATE"),
+            rax: register("rax"),
+            rbx: register("rbx"),
+            rcx: register("rcx"),
+            rdx: register("rdx"),
+            rdi: register("rdi"),
+            rsi: register("rsi"),
+            rbp: register("rbp"),
+            rsp: register("rsp"),
+            r8: register("r8"),
+            r9: register("r9"),
+            r10: register("r10"),
+            r11: register("r11"),
+            r12: register("r12"),
+            r13: register("r13"),
+            r14: register("r14"),
+            r15: register("r15"),
+            rip: register("rip"),
+            rflags: register("rfl"),
+            cr2: register("cr2"),
+            ..Default::default()
+        };
+
+        return (ThreadState::X86_64(Box::new(state)), i);
+    }
+
+    let x_count: usize = registers
+        .keys()
+        .filter_map(|name| name.strip_prefix('x')?.parse::<usize>().ok())
+        .max()
+        .map_or(0, |max| max + 1);
+    let x: Vec<Register> = (0..x_count)
+        .map(|i| register(format!("x{i}").as_str()))
+        .collect();
+
+    let state: Arm64ThreadState = Arm64ThreadState {
+        flavor: String::from("ARM_THREAD_STATE64"),
+        lr: register("lr"),
+        cpsr: register("cpsr"),
+        fp: register("fp"),
+        sp: register("sp"),
+        esr: Esr {
+            value: registers.get("esr").copied().unwrap_or_default(),
+            description: esr_description,
+        },
+        pc: register("pc"),
+        far: register("far"),
+        x,
+    };
+
+    (ThreadState::Arm64(Box::new(state)), i)
+}
+
+// The x86 trap number, error code and logical CPU are printed as header-like
+// fields after the register block
+fn fill_x86_trap_info(state: &mut X86ThreadState, fields: &HashMap<&str, &str>) {
+    if let Some(trap) = fields.get("Trap Number") {
+        state.trap.value = trap.parse().unwrap_or_default();
+    }
+    if let Some(error_code) = fields.get("Error Code") {
+        // "0x00000004 (no mapping for user data read)"
+        let (code, description) = error_code.split_once(' ').unwrap_or((error_code, ""));
+        state.err.value = parse_hex(code).unwrap_or_default();
+        if !description.is_empty() {
+            state.trap.description = Some(description.trim().to_string());
+        }
+    }
+    if let Some(cpu) = fields.get("Logical CPU") {
+        state.cpu = Some(Register {
+            value: cpu.parse().unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+}
+
+fn parse_exception(fields: &HashMap<&str, &str>) -> Exception {
+    // "EXC_BAD_ACCESS (SIGSEGV)"
+    let exception_type: &str = fields.get("Exception Type").unwrap_or(&"");
+    let (r#type, signal) = match TRAILING_PARENS.captures(exception_type) {
+        Some(c) => (c[1].to_string(), c[2].to_string()),
+        None => (exception_type.to_string(), String::new()),
+    };
+
+    let codes: String = fields.get("Exception Codes").unwrap_or(&"").to_string();
+    let raw_codes: Vec<u64> = codes
+        .split(',')
+        .filter_map(|code| parse_hex(code.trim()))
+        .collect();
+
+    Exception {
+        codes,
+        raw_codes,
+        r#type,
+        signal,
+        subtype: fields.get("Exception Subtype").map(|s| s.to_string()),
+    }
+}
+
+fn parse_termination(fields: &HashMap<&str, &str>) -> Option<Termination> {
+    let reason: &str = fields.get("Termination Reason")?;
+
+    let mut termination: Termination = Termination::default();
+    if let Some(rest) = reason.strip_prefix("Namespace ") {
+        // "Namespace SPRINGBOARD, Code 0x8badf00d"
+        let (namespace, code) = rest.split_once(',').unwrap_or((rest, ""));
+        termination.namespace = namespace.trim().to_string();
+        termination.code = parse_code(code.trim().trim_start_matches("Code").trim());
+        termination.indicator = fields.get("Termination Description").map(|d| d.to_string());
+    } else {
+        // "SIGNAL 5 Trace/BPT trap: 5"
+        let mut parts = reason.splitn(3, ' ');
+        termination.namespace = parts.next().unwrap_or_default().to_string();
+        termination.code = parse_code(parts.next().unwrap_or_default());
+        termination.indicator = parts
+            .next()
+            .map(|i| i.trim().to_string())
+            .or_else(|| fields.get("Termination Description").map(|d| d.to_string()));
+    }
+
+    if let Some(by) = fields.get("Terminating Process") {
+        let (by_proc, by_pid) = split_name_and_pid(by);
+        termination.by_proc = Some(by_proc);
+        termination.by_pid = by_pid;
+    }
+
+    Some(termination)
+}
+
+// "launchd [1]" -> ("launchd", Some(1))
+fn split_name_and_pid(value: &str) -> (String, Option<u32>) {
+    match NAME_AND_PID.captures(value) {
+        Some(c) => (c[1].to_string(), c[2].parse().ok()),
+        None => (value.to_string(), None),
+    }
+}
+
+fn parse_hex(value: &str) -> Option<u64> {
+    u64::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
+fn parse_code(value: &str) -> i64 {
+    if value.starts_with("0x") {
+        parse_hex(value).unwrap_or_default() as i64
+    } else {
+        value.parse().unwrap_or_default()
+    }
+}
+
+// Legacy reports print UUIDs as 32 hex digits: "26e65a2808613213..." -> "26e65a28-0861-3213-..."
+fn normalize_uuid(uuid: &str) -> String {
+    let hex: String = uuid.replace('-', "").to_lowercase();
+    if hex.len() != 32 {
+        return hex;
+    }
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,39 @@
+use serde_json::Deserializer;
+
+use super::{ParseError, ParsedReport};
+use crate::structs::crash_info::CrashInfo;
+use crate::structs::incident_report::IncidentReport;
+
+// Parses a modern .ips report.
+// The file is made of two concatenated JSON documents: a one-line incident
+// header followed by the crash body. The header is read with a streaming
+// deserializer so that nested objects or '}' inside strings don't matter,
+// and the body is parsed from the exact byte where the header ended.
+pub fn parse(data: &str) -> Result<ParsedReport, ParseError> {
+    if data.trim().is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    let mut stream = Deserializer::from_str(data).into_iter::<IncidentReport>();
+    let general_info: IncidentReport = match stream.next() {
+        Some(Ok(header)) => header,
+        Some(Err(e)) => return Err(ParseError::Header(e)),
+        None => return Err(ParseError::Empty),
+    };
+    let header_end: usize = stream.byte_offset();
+
+    let body: &str = &data[header_end..];
+    if body.trim().is_empty() {
+        return Err(ParseError::MissingBody);
+    }
+
+    let crash_info: CrashInfo = serde_json::from_str(body).map_err(|source| ParseError::Body {
+        offset: header_end,
+        source,
+    })?;
+
+    Ok(ParsedReport {
+        general_info,
+        crash_info,
+    })
+}
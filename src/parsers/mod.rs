@@ -0,0 +1,63 @@
+pub mod crash;
+pub mod ips;
+
+use std::fmt;
+
+use crate::structs::crash_info::CrashInfo;
+use crate::structs::incident_report::IncidentReport;
+
+// Result of parsing a crash report, whatever the input format
+pub struct ParsedReport {
+    pub general_info: IncidentReport,
+    pub crash_info: CrashInfo,
+}
+
+// Parses a crash report, picking the parser from the content:
+// .ips reports start with a JSON header, legacy .crash reports are plain text
+pub fn parse(data: &str) -> Result<ParsedReport, ParseError> {
+    if data.trim_start().starts_with('{') {
+        ips::parse(data)
+    } else {
+        crash::parse(data)
+    }
+}
+
+// Errors returned by the report parsers
+#[derive(Debug)]
+pub enum ParseError {
+    // The input contains nothing but whitespace
+    Empty,
+    // The incident header (first JSON document) is malformed
+    Header(serde_json::Error),
+    // Nothing follows the incident header
+    MissingBody,
+    // The crash body (second JSON document) is malformed
+    Body { offset: usize, source: serde_json::Error },
+    // A plain-text report lacks a mandatory field or section
+    MissingField(&'static str),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "report is empty"),
+            ParseError::Header(e) => write!(f, "failed to parse incident header: {e}"),
+            ParseError::MissingBody => write!(f, "report has no crash body after the header"),
+            ParseError::Body { offset, source } => write!(
+                f,
+                "failed to parse crash body (starting at byte {offset}): {source}"
+            ),
+            ParseError::MissingField(name) => write!(f, "report has no \"{name}\" field"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Header(e) => Some(e),
+            ParseError::Body { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::structs::crash_info::{CrashInfo, UsedImage};
+
+// Strips arm64e pointer authentication codes (PAC) from signed pointers.
+// The signature lives in the bits above the virtual address size, so
+// canonicalizing a pointer only needs the VA width of the crashed process.
+#[derive(Debug, Clone, Copy)]
+pub struct PointerAuth {
+    va_bits: u32,
+}
+
+impl PointerAuth {
+    // Returns a PointerAuth instance when the process ran on an arm64e (or arm64_32) device.
+    // The VA width depends on the device class: 39 bits for embedded devices
+    // (iOS, iPadOS, tvOS), 47 bits for macOS and 32 bits for arm64_32 watches.
+    pub fn new(cpu_type: &str, is_embedded: bool, images: &[UsedImage]) -> Option<PointerAuth> {
+        if !cpu_type.starts_with("ARM-64") {
+            return None;
+        }
+
+        let has_arch = |arch: &str| images.iter().any(|image| image.arch.as_deref() == Some(arch));
+        let va_bits: u32 = if has_arch("arm64_32") {
+            32
+        } else if !has_arch("arm64e") {
+            return None;
+        } else if is_embedded {
+            39
+        } else {
+            47
+        };
+
+        Some(PointerAuth { va_bits })
+    }
+
+    pub fn for_report(crash_info: &CrashInfo) -> Option<PointerAuth> {
+        Self::new(
+            &crash_info.cpu_type,
+            crash_info.os_version.is_embedded,
+            &crash_info.used_images,
+        )
+    }
+
+    // Removes the PAC signature from a pointer
+    pub fn strip(&self, value: u64) -> u64 {
+        value & ((1u64 << self.va_bits) - 1)
+    }
+}
@@ -0,0 +1,472 @@
+use r2pipe::R2Pipe;
+use std::collections::HashSet;
+
+use crate::arm64::{canonical_register, Instruction, MemoryOperand, Offset};
+
+// Definitions followed backwards at most
+const MAX_STEPS: usize = 16;
+
+// An instruction of the analyzed function
+struct FunctionInstruction {
+    address: u64,
+    text: String,
+    instruction: Instruction,
+}
+
+// A basic block, as indexes in the instructions of the function
+struct BasicBlock {
+    address: u64,
+    first: usize,
+    last: usize,
+    predecessors: Vec<usize>,
+}
+
+struct Function {
+    start: u64,
+    instructions: Vec<FunctionInstruction>,
+    blocks: Vec<BasicBlock>,
+}
+
+// A value followed backwards: a register or a stack slot it was spilled to
+#[derive(Debug, Clone, PartialEq)]
+enum Tracked {
+    Register(String),
+    StackSlot { base: String, offset: i64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Definition {
+    Instruction(usize),
+    // Not set in the function: an argument, a callee-saved register, ...
+    Entry,
+    // Reached a block without predecessors that is not the entry
+    Unknown,
+}
+
+// Explains where the register used by the faulting instruction at `address`
+// comes from (the base register of a memory access, or the target of an
+// indirect branch): walks the basic blocks of its function backwards,
+// through register copies, loads and stack slots, up to an argument, the
+// return value of a call, or any other instruction setting it.
+pub fn trace(r2: &mut R2Pipe, address: u64, function_start: Option<u64>) -> String {
+    let function: Function = match load_function(r2, address, function_start) {
+        Some(function) => function,
+        None => return format!("{:<10} Provenance: no function analyzed at {:#x}\n", "", address),
+    };
+    let position: usize = match function.instructions.iter().position(|i| i.address == address) {
+        Some(position) => position,
+        None => return format!("{:<10} Provenance: {:#x} is not an instruction of the function\n", "", address),
+    };
+
+    let faulting: &Instruction = &function.instructions[position].instruction;
+    let (mut tracked, role) = match faulting.memory_operand() {
+        Some(operand) => (
+            Tracked::Register(canonical_register(&operand.base)),
+            "base register of the faulting access",
+        ),
+        None if faulting.mnemonic.starts_with("br") || faulting.mnemonic.starts_with("blr") => {
+            match faulting.operands.first() {
+                Some(target) => (Tracked::Register(canonical_register(target)), "target of the branch"),
+                None => return String::new(),
+            }
+        }
+        None => {
+            return format!(
+                "{:<10} Provenance: the crashing instruction does not access memory\n",
+                ""
+            )
+        }
+    };
+
+    let mut res: String = format!("{:<10} Provenance of {} ({}):\n", "", tracked_name(&tracked), role);
+    let mut position: usize = position;
+    for _ in 0..MAX_STEPS {
+        let definitions: Vec<Definition> = function.definitions(&tracked, position);
+
+        if definitions.len() > 1 {
+            res.push_str(format!("{:<12} {} is set on several paths:\n", "", tracked_name(&tracked)).as_str());
+            for definition in &definitions {
+                res.push_str(function.describe(definition, &tracked).0.as_str());
+            }
+            break;
+        }
+
+        let definition: &Definition = match definitions.first() {
+            Some(definition) => definition,
+            None => break,
+        };
+        let (line, next) = function.describe(definition, &tracked);
+        res.push_str(line.as_str());
+
+        match (definition, next) {
+            (Definition::Instruction(index), Some(next)) => {
+                position = *index;
+                tracked = next;
+            }
+            _ => break,
+        }
+    }
+
+    res
+}
+
+fn load_function(r2: &mut R2Pipe, address: u64, function_start: Option<u64>) -> Option<Function> {
+    // Code mapped from a dyld_shared_cache is not analyzed up front
+    if let Some(start) = function_start {
+        r2.cmd(format!("af @ {:#x}", start).as_str()).ok()?;
+    }
+
+    let info: serde_json::Value = r2.cmdj(format!("afij @ {:#x}", address).as_str()).ok()?;
+    let start: u64 = info.get(0)?["offset"].as_u64()?;
+
+    let ops: serde_json::Value = r2.cmdj(format!("pdfj @ {:#x}", start).as_str()).ok()?;
+    let mut instructions: Vec<FunctionInstruction> = ops["ops"]
+        .as_array()?
+        .iter()
+        .filter_map(|op| {
+            let text: &str = op["opcode"].as_str()?;
+            Some(FunctionInstruction {
+                address: op["offset"].as_u64()?,
+                text: op["disasm"].as_str().unwrap_or(text).to_string(),
+                instruction: Instruction::parse(text)?,
+            })
+        })
+        .collect();
+    instructions.sort_by_key(|i| i.address);
+
+    let blocks: serde_json::Value = r2.cmdj(format!("afbj @ {:#x}", start).as_str()).ok()?;
+    let blocks: &Vec<serde_json::Value> = blocks.as_array()?;
+    let ranges: Vec<(u64, u64, Vec<u64>)> = blocks
+        .iter()
+        .filter_map(|block| {
+            let address: u64 = block["addr"].as_u64()?;
+            let size: u64 = block["size"].as_u64()?;
+            let successors: Vec<u64> = [&block["jump"], &block["fail"]]
+                .iter()
+                .filter_map(|s| s.as_u64())
+                .collect();
+            Some((address, address + size, successors))
+        })
+        .collect();
+
+    let mut basic_blocks: Vec<BasicBlock> = ranges
+        .iter()
+        .filter_map(|(address, end, _)| {
+            let first: usize = instructions.iter().position(|i| i.address == *address)?;
+            let last: usize = instructions.iter().rposition(|i| i.address < *end)?;
+            Some(BasicBlock {
+                address: *address,
+                first,
+                last,
+                predecessors: Vec::new(),
+            })
+        })
+        .collect();
+    for (address, _, successors) in &ranges {
+        let from: usize = match basic_blocks.iter().position(|b| b.address == *address) {
+            Some(from) => from,
+            None => continue,
+        };
+        for successor in successors {
+            if let Some(to) = basic_blocks.iter().position(|b| b.address == *successor) {
+                basic_blocks[to].predecessors.push(from);
+            }
+        }
+    }
+
+    Some(Function {
+        start,
+        instructions,
+        blocks: basic_blocks,
+    })
+}
+
+impl Function {
+    fn block_of(&self, index: usize) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|b| b.first <= index && index <= b.last)
+    }
+
+    // Instructions that may have set the tracked value last before the
+    // instruction at `index`, on every path leading to it
+    fn definitions(&self, tracked: &Tracked, index: usize) -> Vec<Definition> {
+        let mut definitions: Vec<Definition> = Vec::new();
+        let mut visited: HashSet<usize> = HashSet::new();
+        let mut pending: Vec<(usize, usize)> = match self.block_of(index) {
+            Some(block) => vec![(block, index)],
+            None => return vec![Definition::Unknown],
+        };
+
+        while let Some((block, end)) = pending.pop() {
+            let first: usize = self.blocks[block].first;
+            let found: Option<usize> = (first..end)
+                .rev()
+                .find(|i| defines(&self.instructions[*i].instruction, tracked));
+
+            let definition: Option<Definition> = match found {
+                Some(i) => Some(Definition::Instruction(i)),
+                None if self.blocks[block].predecessors.is_empty() => {
+                    Some(if self.blocks[block].address == self.start {
+                        Definition::Entry
+                    } else {
+                        Definition::Unknown
+                    })
+                }
+                None => {
+                    for predecessor in &self.blocks[block].predecessors {
+                        if visited.insert(*predecessor) {
+                            pending.push((*predecessor, self.blocks[*predecessor].last + 1));
+                        }
+                    }
+                    None
+                }
+            };
+            if let Some(definition) = definition.filter(|d| !definitions.contains(d)) {
+                definitions.push(definition);
+            }
+        }
+
+        definitions
+    }
+
+    // Line explaining a definition, and what to follow next
+    fn describe(&self, definition: &Definition, tracked: &Tracked) -> (String, Option<Tracked>) {
+        let index: usize = match definition {
+            Definition::Instruction(index) => *index,
+            Definition::Entry => {
+                return (
+                    format!("{:<12} {:<12}  {}\n", "", "entry", entry_description(tracked)),
+                    None,
+                )
+            }
+            Definition::Unknown => {
+                return (
+                    format!("{:<12} {:<12}  {} comes from code outside the function\n", "", "?", tracked_name(tracked)),
+                    None,
+                )
+            }
+        };
+
+        let FunctionInstruction {
+            address,
+            text,
+            instruction,
+        } = &self.instructions[index];
+        let (description, next) = describe_instruction(instruction, tracked);
+
+        (
+            format!("{:<12} {:#x}  {:<36} {}\n", "", address, text, description),
+            next,
+        )
+    }
+}
+
+// Whether an instruction sets the tracked value
+fn defines(instruction: &Instruction, tracked: &Tracked) -> bool {
+    match tracked {
+        Tracked::Register(register) => {
+            (instruction.is_call() && is_caller_saved(register))
+                || instruction
+                    .written_registers()
+                    .iter()
+                    .any(|r| canonical_register(r) == *register)
+        }
+        // Moving the base register moves the slot too
+        Tracked::StackSlot { base, .. } => {
+            stored_register(instruction, tracked).is_some()
+                || instruction
+                    .writeback()
+                    .is_some_and(|writeback| canonical_register(&writeback.base) == *base)
+        }
+    }
+}
+
+fn describe_instruction(instruction: &Instruction, tracked: &Tracked) -> (String, Option<Tracked>) {
+    let register: &String = match tracked {
+        Tracked::Register(register) => register,
+        Tracked::StackSlot { base, offset } => {
+            return match (stored_register(instruction, tracked), instruction.writeback()) {
+                (Some(source), _) => (
+                    format!("stored from {}", source),
+                    Some(Tracked::Register(canonical_register(&source))),
+                ),
+                // The slot, relative to the base before the update
+                (None, Some(writeback)) => {
+                    let offset: i64 = offset + writeback.amount;
+                    (
+                        format!(
+                            "{} moved by {}, the slot was [{}, {}]",
+                            writeback.base,
+                            format_offset(writeback.amount),
+                            writeback.base,
+                            format_offset(offset)
+                        ),
+                        Some(Tracked::StackSlot { base: base.clone(), offset }),
+                    )
+                }
+                (None, None) => (String::from("set by this instruction"), None),
+            };
+        }
+    };
+    let target: &str = instruction.operands.first().map_or("?", |t| t.as_str());
+
+    if instruction.is_call() {
+        return if register == "x0" {
+            (format!("return value of the call to {}", target), None)
+        } else {
+            (format!("{} is clobbered by the call to {}", register, target), None)
+        };
+    }
+
+    // Base register of an access with writeback, that the access did not load
+    if let Some(writeback) = instruction
+        .writeback()
+        .filter(|writeback| canonical_register(&writeback.base) == *register)
+    {
+        let loaded: bool = instruction
+            .written_registers()
+            .iter()
+            .filter(|r| **r != writeback.base)
+            .any(|r| canonical_register(r) == *register);
+        if !loaded {
+            return (
+                format!("{} moved by {} (writeback)", writeback.base, format_offset(writeback.amount)),
+                Some(Tracked::Register(register.clone())),
+            );
+        }
+    }
+
+    let mnemonic: &str = instruction.mnemonic.as_str();
+    let operands: &Vec<String> = &instruction.operands;
+    match (mnemonic, instruction.memory_operand()) {
+        (_, Some(operand)) if instruction.is_load() => {
+            // Second register of a pair load: next slot
+            let position: i64 = instruction
+                .written_registers()
+                .iter()
+                .position(|r| canonical_register(r) == *register)
+                .unwrap_or(0) as i64;
+            let size: i64 = if target.starts_with('w') { 4 } else { 8 };
+            let base: String = canonical_register(&operand.base);
+
+            match operand.offset {
+                Offset::Immediate(offset) if base == "sp" || base == "x29" => {
+                    let offset: i64 = offset + position * size;
+                    (
+                        format!("loaded from the stack slot [{}, {}]", operand.base, format_offset(offset)),
+                        Some(Tracked::StackSlot { base, offset }),
+                    )
+                }
+                _ => (
+                    format!("loaded from {}, {} holds the pointer", format_operand(&operand), operand.base),
+                    Some(Tracked::Register(base)),
+                ),
+            }
+        }
+        ("mov", None) if operands.len() == 2 && operands[1].starts_with(['x', 'w']) => (
+            format!("copied from {}", operands[1]),
+            Some(Tracked::Register(canonical_register(&operands[1]))),
+        ),
+        ("mov", None) if operands.len() == 2 => (format!("set to the constant {}", operands[1]), None),
+        ("add" | "sub", None) if operands.len() == 3 && !operands[2].starts_with(['x', 'w']) => (
+            format!(
+                "{} {} {}",
+                operands[1],
+                if mnemonic == "add" { "+" } else { "-" },
+                operands[2]
+            ),
+            Some(Tracked::Register(canonical_register(&operands[1]))),
+        ),
+        ("adrp" | "adr", None) => (format!("address {}", operands.get(1).map_or("?", |o| o.as_str())), None),
+        _ => (String::from("set by this instruction"), None),
+    }
+}
+
+// Register a store writes to the tracked stack slot
+fn stored_register(instruction: &Instruction, tracked: &Tracked) -> Option<String> {
+    let (base, offset) = match tracked {
+        Tracked::StackSlot { base, offset } => (base, *offset),
+        Tracked::Register(_) => return None,
+    };
+    if !instruction.is_store() {
+        return None;
+    }
+    let operand: MemoryOperand = instruction.memory_operand()?;
+    if canonical_register(&operand.base) != *base {
+        return None;
+    }
+    // Offset from the base as it is after the instruction, which the slot
+    // is relative to
+    let store_offset: i64 = match (operand.offset, instruction.writeback()) {
+        (Offset::Immediate(_), Some(writeback)) if writeback.pre_indexed => 0,
+        (Offset::Immediate(offset), Some(writeback)) => offset - writeback.amount,
+        (Offset::Immediate(offset), None) => offset,
+        (Offset::Register(..), _) => return None,
+    };
+
+    // Registers stored, before the memory operand ("stp x19, x20, [sp, 0x10]")
+    let stored: Vec<&str> = instruction.stored_registers();
+    let size: i64 = if stored.first()?.starts_with('w') { 4 } else { 8 };
+
+    stored
+        .iter()
+        .enumerate()
+        .find(|(n, _)| store_offset + *n as i64 * size == offset)
+        .map(|(_, register)| register.to_string())
+}
+
+// x0-x18 and lr are not preserved across calls
+fn is_caller_saved(register: &str) -> bool {
+    register == "x30"
+        || register
+            .strip_prefix('x')
+            .and_then(|n| n.parse::<u32>().ok())
+            .is_some_and(|n| n <= 18)
+}
+
+fn entry_description(tracked: &Tracked) -> String {
+    let register: &str = match tracked {
+        Tracked::Register(register) => register,
+        Tracked::StackSlot { base, offset } => {
+            return format!("stack slot [{}, {}] is not written by the function", base, format_offset(*offset))
+        }
+    };
+    let number: Option<u32> = register.strip_prefix('x').and_then(|n| n.parse().ok());
+
+    match (register, number) {
+        (_, Some(n)) if n <= 7 => format!("{} is argument {} of the function", register, n + 1),
+        (_, Some(n)) if (19..=29).contains(&n) => {
+            format!("{} is callee-saved, it holds the value of the caller", register)
+        }
+        ("x30", _) => String::from("x30 is the return address"),
+        ("sp", _) => String::from("sp is the stack pointer of the caller"),
+        _ => format!("{} is not set by the function", register),
+    }
+}
+
+fn tracked_name(tracked: &Tracked) -> String {
+    match tracked {
+        Tracked::Register(register) => register.clone(),
+        Tracked::StackSlot { base, offset } => format!("[{}, {}]", base, format_offset(*offset)),
+    }
+}
+
+fn format_operand(operand: &MemoryOperand) -> String {
+    match &operand.offset {
+        Offset::Immediate(0) => format!("[{}]", operand.base),
+        Offset::Immediate(offset) => format!("[{}, {}]", operand.base, format_offset(*offset)),
+        Offset::Register(index, Some(extend), shift) => {
+            format!("[{}, {}, {} {}]", operand.base, index, extend, shift)
+        }
+        Offset::Register(index, None, _) => format!("[{}, {}]", operand.base, index),
+    }
+}
+
+fn format_offset(offset: i64) -> String {
+    if offset < 0 {
+        format!("-{:#x}", offset.unsigned_abs())
+    } else {
+        format!("{:#x}", offset)
+    }
+}
@@ -1,10 +1,26 @@
-use r2pipe::R2Pipe;
+use r2pipe::{R2Pipe, R2PipeSpawnOptions};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::path::Path;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process;
 
-// Struct that maintains a cache of R2Pipe instances associated with binary file names
+use crate::byte_stream;
+use crate::dyld_cache::CacheMapping;
+use crate::macho::MachOSlice;
+
+// Struct that maintains a cache of R2Pipe instances associated with binary slices.
+// Sessions are keyed by the canonical path of the binary plus the offset of the
+// slice, so that images sharing a file name (an app framework and a system
+// library, or two slices of the same fat binary) never share a session.
+// r2 selects the slices of fat files by arch and bits only, which can't tell
+// arm64 from arm64e: slices of fat files are copied to a temporary file of
+// their own, removed when the cache is dropped, and r2 opens that copy.
 pub struct R2PipeCache {
     map: HashMap<String, R2Pipe>,
+    extracted: Vec<PathBuf>,
 }
 
 impl R2PipeCache {
@@ -12,26 +28,174 @@ impl R2PipeCache {
     pub fn new() -> Self {
         Self {
             map: HashMap::new(),
+            extracted: Vec::new(),
         }
     }
 
-    // Returns a mutable reference to the R2Pipe instance for a given binary path.
-    // If it doesn't exist in the cache, it's created and initialized.
-    pub fn get_or_create(&mut self, image_path: &str) -> &mut R2Pipe {
-        // Extract binary name from full path
-        let binary_name: String = Path::new(image_path)
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
+    // Returns a mutable reference to the R2Pipe instance for a given binary path
+    // and slice. If it doesn't exist in the cache, it's created and initialized.
+    // Without a slice (the file is not a readable Mach-O), r2 picks the one
+    // matching `arch`.
+    pub fn get_or_create(
+        &mut self,
+        image_path: &str,
+        arch: Option<&str>,
+        slice: Option<&MachOSlice>,
+    ) -> Result<&mut R2Pipe, r2pipe::Error> {
+        let canonical_path: String = fs::canonicalize(image_path)
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| image_path.to_string());
+        let key: String = match slice {
+            Some(slice) => format!("{}@{:#x}", canonical_path, slice.offset),
+            None => session_key(&canonical_path, arch),
+        };
 
         // Open R2Pipe only if it's not already in the cache
-        self.map.entry(binary_name).or_insert_with(|| {
-            let mut r2: R2Pipe = open_pipe!(Some(image_path)).expect("Failed to open r2pipe");
-            r2.cmd("aa").unwrap();
+        match self.map.entry(key) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let path: String = match slice {
+                    Some(slice) => extract_slice(&mut self.extracted, Path::new(&canonical_path), slice)?,
+                    None => canonical_path,
+                };
+                let mut r2: R2Pipe = R2Pipe::spawn(
+                    path,
+                    Some(R2PipeSpawnOptions {
+                        args: slice_args(arch),
+                        ..Default::default()
+                    }),
+                )?;
+                r2.cmd("aa")?;
+
+                Ok(entry.insert(r2))
+            }
+        }
+    }
+
+    // Same as get_or_create for a dyld_shared_cache file: the file is opened raw
+    // and its mappings are mapped at their unslid addresses. The cache is not
+    // analyzed as a whole, it is far too big for that.
+    pub fn get_or_create_mapped(
+        &mut self,
+        cache_path: &str,
+        arch: Option<&str>,
+        mappings: &[CacheMapping],
+    ) -> Result<&mut R2Pipe, r2pipe::Error> {
+        let canonical_path: String = fs::canonicalize(cache_path)
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| cache_path.to_string());
+        let key: String = session_key(&canonical_path, arch);
+
+        match self.map.entry(key) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let mut args: Vec<&'static str> = vec!["-n"];
+                args.extend(slice_args(arch));
+                let mut r2: R2Pipe = R2Pipe::spawn(
+                    canonical_path,
+                    Some(R2PipeSpawnOptions {
+                        args,
+                        ..Default::default()
+                    }),
+                )?;
+
+                let fd: String = r2.cmd("o.")?;
+                r2.cmd("om-*")?;
+                for mapping in mappings {
+                    r2.cmd(
+                        format!(
+                            "om {} {:#x} {:#x} {:#x} r-x",
+                            fd.trim(),
+                            mapping.address,
+                            mapping.size,
+                            mapping.file_offset
+                        )
+                        .as_str(),
+                    )?;
+                }
+
+                Ok(entry.insert(r2))
+            }
+        }
+    }
+}
+
+impl Drop for R2PipeCache {
+    fn drop(&mut self) {
+        for path in &self.extracted {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Path of a file holding only `slice`: the binary itself when it is thin,
+// otherwise a temporary copy of the slice, recorded in `extracted`
+fn extract_slice(extracted: &mut Vec<PathBuf>, path: &Path, slice: &MachOSlice) -> io::Result<String> {
+    if slice.offset == 0 && slice.size == fs::metadata(path)?.len() {
+        return Ok(path.display().to_string());
+    }
+
+    let mut file: File = File::open(path)?;
+    file.seek(SeekFrom::Start(slice.offset))?;
+    let mut bytes: Vec<u8> = vec![0; slice.size as usize];
+    file.read_exact(&mut bytes)?;
+
+    let name: &str = path.file_name().and_then(|n| n.to_str()).unwrap_or("binary");
+    let copy: PathBuf = env::temp_dir().join(format!(
+        "assemblicate-{}-slice{}-{}-{}",
+        process::id(),
+        extracted.len(),
+        slice.arch,
+        name
+    ));
+    fs::write(&copy, bytes)?;
+    extracted.push(copy.clone());
+
+    Ok(copy.display().to_string())
+}
+
+// Opens a r2 session on a copy of `bytes` mapped at `address`, for code that
+// is not backed by any binary. Not cached: each copy is used once.
+pub fn open_bytes(bytes: &[u8], address: u64, arch: Option<&str>) -> Result<R2Pipe, r2pipe::Error> {
+    let mut args: Vec<&'static str> = vec!["-w", "-n"];
+    args.extend(slice_args(arch));
+    let mut r2: R2Pipe = R2Pipe::spawn(
+        format!("malloc://{}", bytes.len()),
+        Some(R2PipeSpawnOptions {
+            args,
+            ..Default::default()
+        }),
+    )?;
+
+    r2.cmd(format!("wx {} @ 0", byte_stream::format_bytes(bytes)).as_str())?;
+    let fd: String = r2.cmd("o.")?;
+    r2.cmd("om-*")?;
+    r2.cmd(format!("om {} {:#x} {:#x} 0 r-x", fd.trim(), address, bytes.len()).as_str())?;
+
+    Ok(r2)
+}
+
+// Key of the sessions opened without a slice (dyld_shared_cache files, files
+// that are not readable Mach-O): the ones opened with the same arch and bits
+// share it
+fn session_key(canonical_path: &str, arch: Option<&str>) -> String {
+    format!("{}#{}", canonical_path, slice_args(arch).join(" "))
+}
+
+// Whether r2 selects the slices of two architectures with the same arch and
+// bits, and so can't tell them apart in a fat file
+pub fn same_r2_arch(arch: &str, other: &str) -> bool {
+    let args: Vec<&'static str> = slice_args(Some(arch));
+    !args.is_empty() && args == slice_args(Some(other))
+}
 
-            r2
-        })
+// radare2 arguments selecting the slice of a fat binary
+fn slice_args(arch: Option<&str>) -> Vec<&'static str> {
+    match arch {
+        Some("arm64" | "arm64e") => vec!["-a", "arm", "-b", "64"],
+        Some("arm64_32" | "armv7" | "armv7s" | "armv7k") => vec!["-a", "arm", "-b", "32"],
+        Some("x86_64" | "x86_64h") => vec!["-a", "x86", "-b", "64"],
+        Some("i386") => vec!["-a", "x86", "-b", "32"],
+        _ => Vec::new(),
     }
 }
@@ -1,15 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct OSVersion {
-    is_embedded: bool,
+    pub is_embedded: bool,
     pub train: String,
-    release_type: String,
+    pub release_type: String,
     pub build: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Exception {
     pub codes: String,
@@ -19,30 +19,30 @@ pub struct Exception {
     pub subtype: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Termination {
-    flags: i32,
-    code: i64,
-    namespace: String,
+    pub flags: i32,
+    pub code: i64,
+    pub namespace: String,
     pub indicator: Option<String>,
-    by_proc: Option<String>,
-    by_pid: Option<u32>,
+    pub by_proc: Option<String>,
+    pub by_pid: Option<u32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone)]
 pub struct Thread {
-    id: u32,
-    name: Option<String>,
+    pub id: u32,
+    pub name: Option<String>,
     pub triggered: Option<bool>,
     pub frames: Vec<Frame>,
     pub thread_state: Option<ThreadState>,
-    queue: Option<String>,
+    pub queue: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone)]
 pub struct Frame {
@@ -52,8 +52,16 @@ pub struct Frame {
     pub image_index: u32,
 }
 
+// Register state of a thread. The layout depends on the CPU the report comes from
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ThreadState {
+#[serde(untagged)]
+pub enum ThreadState {
+    Arm64(Box<Arm64ThreadState>),
+    X86_64(Box<X86ThreadState>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Arm64ThreadState {
     pub flavor: String,
     pub lr: Register,
     pub cpsr: Register,
@@ -65,42 +73,77 @@ pub struct ThreadState {
     pub x: Vec<Register>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct X86ThreadState {
+    pub flavor: String,
+    pub rax: Register,
+    pub rbx: Register,
+    pub rcx: Register,
+    pub rdx: Register,
+    pub rdi: Register,
+    pub rsi: Register,
+    pub rbp: Register,
+    pub rsp: Register,
+    pub r8: Register,
+    pub r9: Register,
+    pub r10: Register,
+    pub r11: Register,
+    pub r12: Register,
+    pub r13: Register,
+    pub r14: Register,
+    pub r15: Register,
+    pub rip: Register,
+    pub rflags: Register,
+    pub cr2: Register,
+    pub trap: Trap,
+    pub err: Register,
+    pub cpu: Option<Register>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone)]
 pub struct Register {
     pub value: u64,
-    symbol_location: Option<u64>,
+    pub symbol_location: Option<u64>,
     pub symbol: Option<String>,
     #[serde(alias = "objc-selector")]
     pub objc_selector: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Esr {
     pub value: u64,
-    description: String,
+    #[serde(default)]
+    pub description: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Trap {
+    pub value: u64,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct UsedImage {
-    source: String,
-    arch: Option<String>,
+    pub source: String,
+    pub arch: Option<String>,
     pub base: u64,
-    size: u64,
-    uuid: String,
+    pub size: u64,
+    pub uuid: String,
     pub path: Option<String>,
     pub name: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SharedCache {
-    base: u64,
-    size: u64,
-    uuid: String,
+    pub base: u64,
+    pub size: u64,
+    pub uuid: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// Types of the legacy_info and trial_info fields, not parsed yet
+/*#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LegacyInfo {
     thread_triggered: ThreadTriggered,
@@ -131,17 +174,18 @@ pub struct FactorPackIds {
     siri_morphun_assets: Option<String>,
     #[serde(alias = "SIRI_HOME_AUTOMATION_INTENT_SELECTION_CACHE")]
     siri_home_automation_intent_selection_cache: Option<String>,
-}
+}*/
 
-#[derive(Debug, Serialize, Deserialize)]
-struct InstructionByteStream {
+// Base64 bytes of memory around the pc of the crashing thread
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstructionByteStream {
     #[serde(alias = "beforePC")]
-    before_pc: String,
+    pub before_pc: String,
     #[serde(alias = "atPC")]
-    at_pc: String,
+    pub at_pc: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CrashInfo {
     /*uptime: u64,
@@ -175,7 +219,7 @@ pub struct CrashInfo {
     pub faulting_thread: u32,
     pub threads: Vec<Thread>,
     pub used_images: Vec<UsedImage>,
-    //shared_cache: SharedCache,
+    pub shared_cache: Option<SharedCache>,
     //vm_summary: String,
     //legacy_info: LegacyInfo,
     //trial_info: TrialInfo,
@@ -191,7 +235,7 @@ pub struct CrashInfo {
     code_signing_flags: Option<i64>,
     code_signing_validation_category: Option<i64>,
     code_signing_trust_level: Option<i64>,
-    instruction_byte_stream: Option<InstructionByteStream>,
     asi: Option<HashMap<String, Vec<String>>>,*/
     pub last_exception_backtrace: Option<Vec<Frame>>,
+    pub instruction_byte_stream: Option<InstructionByteStream>,
 }
@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::structs::crash_info::OSVersion;
+
+// Symbol directory chosen for the OS build of a report
+pub enum SymbolDir {
+    // No symbol store configured
+    NotConfigured,
+    // The report does not carry a usable OS build (e.g. anonymized reports)
+    UnknownBuild,
+    Found(PathBuf),
+    Missing { build: String, stores: Vec<PathBuf> },
+}
+
+// Looks for the symbol directory of an OS build in the given stores, in order.
+// A store holds one directory per OS build, named after the build like Xcode's
+// `iOS DeviceSupport/<version> (<build>)` (optionally followed by the
+// architecture, e.g. `16.3 (20D47) arm64e`) or simply `<build>`.
+pub fn select(stores: &[PathBuf], os_version: &OSVersion) -> SymbolDir {
+    if stores.is_empty() {
+        return SymbolDir::NotConfigured;
+    }
+
+    let build: &str = os_version.build.trim();
+    if build.is_empty() || !build.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return SymbolDir::UnknownBuild;
+    }
+
+    match stores.iter().find_map(|store| find_build_dir(store, build)) {
+        Some(dir) => SymbolDir::Found(dir),
+        None => SymbolDir::Missing {
+            build: build.to_string(),
+            stores: stores.to_vec(),
+        },
+    }
+}
+
+fn find_build_dir(store: &Path, build: &str) -> Option<PathBuf> {
+    let parenthesized: String = format!("({})", build);
+
+    let mut dirs: Vec<PathBuf> = fs::read_dir(store)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name == build || name.contains(&parenthesized))
+        })
+        .collect();
+
+    // Deterministic choice when a build has several directories (one per arch)
+    dirs.sort();
+    dirs.into_iter().next()
+}
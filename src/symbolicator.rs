@@ -0,0 +1,247 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::demangle::Demangler;
+use crate::macho::{MachOImage, MachOSlice};
+use crate::objc::{self, ObjcMethod};
+
+lazy_static! {
+    // "-[Class selector:]", "+[Class(Category) selector]"
+    static ref OBJC_METHOD: Regex =
+        Regex::new(r"^([-+])\[([^ ()\]]+)(?:\(([^)]*)\))? ([^\]]+)\]$").unwrap();
+    // ".cold.1" in symbol tables, " [clone .cold.1]" once demangled by c++filt
+    static ref COLD_SUFFIX: Regex =
+        Regex::new(r"(?:\.cold\.([0-9]+)| \[clone \.cold\.([0-9]+)\])$").unwrap();
+    // "_block_invoke" (first block of a function), "_block_invoke_2", ...
+    static ref BLOCK_INVOKE_SUFFIX: Regex =
+        Regex::new(r"_block_invoke(?:[._]([0-9]+))?$").unwrap();
+    // Prefix of block symbols: "__" and the length of the enclosing name
+    static ref BLOCK_PREFIX: Regex = Regex::new(r"^__[0-9]*").unwrap();
+}
+
+// Symbols of one image, sorted by unslid address. Names come from the symbol
+// table first, then from the ObjC metadata; function starts without a name
+// still delimit functions (named "sub_<address>" like r2 does).
+pub struct SymbolTable {
+    pub text_vmaddr: u64,
+    symbols: Vec<Symbol>,
+    // ObjC methods, also looked up by class and selector when the symbol
+    // table names their implementation differently (or not at all)
+    objc_methods: Vec<ObjcMethod>,
+}
+
+struct Symbol {
+    address: u64,
+    name: String,
+    parts: SymbolName,
+}
+
+// Result of looking a symbol up by name
+pub enum SymbolMatch {
+    // Unslid address of the symbol
+    Found(u64),
+    NotFound,
+    // Several symbols have the name and none contains the frame address
+    Ambiguous(Vec<u64>),
+}
+
+impl SymbolTable {
+    pub fn load(path: &Path, slice: &MachOSlice) -> Option<SymbolTable> {
+        let image: MachOImage = MachOImage::load(path, slice).ok()?;
+        let text_vmaddr: u64 = image.text_vmaddr()?;
+
+        let mut by_address: HashMap<u64, String> = HashMap::new();
+        for (address, name) in image.symbols() {
+            by_address
+                .entry(address)
+                .or_insert(clean_symbol_name(&name));
+        }
+        let objc_methods: Vec<ObjcMethod> = objc::methods(&image);
+        for method in &objc_methods {
+            by_address.entry(method.imp).or_insert_with(|| method.name());
+        }
+        for address in image.function_starts() {
+            by_address
+                .entry(address)
+                .or_insert_with(|| format!("sub_{:x}", address));
+        }
+
+        let mut symbols: Vec<Symbol> = by_address
+            .into_iter()
+            .map(|(address, name)| Symbol {
+                address,
+                parts: SymbolName::parse(&name),
+                name,
+            })
+            .collect();
+        symbols.sort_by_key(|symbol| symbol.address);
+
+        Some(SymbolTable {
+            text_vmaddr,
+            symbols,
+            objc_methods,
+        })
+    }
+
+    // Symbol containing an image offset, with the offset of the address in it
+    pub fn lookup(&self, image_offset: u64) -> Option<(&str, u64)> {
+        let address: u64 = self.text_vmaddr + image_offset;
+        let index: usize = match self.symbols.binary_search_by_key(&address, |s| s.address) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let symbol: &Symbol = &self.symbols[index];
+
+        Some((symbol.name.as_str(), address - symbol.address))
+    }
+
+    // Demangles the names of all symbols at once, for `find` to compare them
+    // with the names of the report
+    pub fn prefetch_names(&self, demangler: &mut Demangler) {
+        let bases: Vec<&str> = self.symbols.iter().map(|s| s.parts.base.as_str()).collect();
+        demangler.prefetch(&bases);
+    }
+
+    // Finds a symbol by the name a crash report gives it (possibly demangled).
+    // Names match exactly, ObjC methods on class, category and selector, blocks
+    // on their index and cold parts on their number. When several symbols
+    // match, the one containing the frame at `image_offset` wins.
+    pub fn find(&self, name: &str, demangler: &mut Demangler, image_offset: u64) -> SymbolMatch {
+        let wanted: SymbolName = SymbolName::parse(name);
+
+        let mut addresses: Vec<u64> = self
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.parts.matches(&wanted, demangler))
+            .map(|symbol| symbol.address)
+            .collect();
+        if let (Some(method), true, None) = (&wanted.objc, wanted.blocks.is_empty(), wanted.cold) {
+            addresses.extend(objc::implementations(
+                &self.objc_methods,
+                &method.class,
+                method.category.as_deref(),
+                &method.selector,
+                method.is_class_method,
+            ));
+        }
+        addresses.sort();
+        addresses.dedup();
+
+        match addresses.len() {
+            0 => SymbolMatch::NotFound,
+            1 => SymbolMatch::Found(addresses[0]),
+            _ => {
+                let containing: Option<u64> = self
+                    .lookup(image_offset)
+                    .map(|(_, offset)| self.text_vmaddr + image_offset - offset)
+                    .filter(|start| addresses.contains(start));
+                match containing {
+                    Some(address) => SymbolMatch::Found(address),
+                    None => SymbolMatch::Ambiguous(addresses),
+                }
+            }
+        }
+    }
+}
+
+// A symbol name split into the parts compared by `SymbolTable::find`
+#[derive(Debug)]
+struct SymbolName {
+    // Name without the block and cold parts
+    base: String,
+    objc: Option<ObjcMethodName>,
+    // Index of each nested block, outermost first ("_block_invoke" is 1)
+    blocks: Vec<u32>,
+    cold: Option<u32>,
+}
+
+#[derive(Debug)]
+struct ObjcMethodName {
+    is_class_method: bool,
+    class: String,
+    category: Option<String>,
+    selector: String,
+}
+
+impl SymbolName {
+    fn parse(name: &str) -> SymbolName {
+        let mut rest: &str = name.trim();
+
+        let mut cold: Option<u32> = None;
+        if let Some(captures) = COLD_SUFFIX.captures(rest) {
+            cold = captures
+                .get(1)
+                .or(captures.get(2))
+                .and_then(|n| n.as_str().parse().ok());
+            rest = &rest[..captures.get(0).unwrap().start()];
+        }
+
+        let mut blocks: Vec<u32> = Vec::new();
+        while let Some(captures) = BLOCK_INVOKE_SUFFIX.captures(rest) {
+            let index: u32 = captures
+                .get(1)
+                .and_then(|n| n.as_str().parse().ok())
+                .unwrap_or(1);
+            blocks.insert(0, index);
+            rest = &rest[..captures.get(0).unwrap().start()];
+        }
+        if !blocks.is_empty() {
+            rest = BLOCK_PREFIX
+                .find(rest)
+                .map_or(rest, |prefix| &rest[prefix.end()..]);
+        }
+
+        let objc: Option<ObjcMethodName> = OBJC_METHOD.captures(rest).map(|captures| ObjcMethodName {
+            is_class_method: &captures[1] == "+",
+            class: captures[2].to_string(),
+            category: captures.get(3).map(|c| c.as_str().to_string()),
+            selector: captures[4].to_string(),
+        });
+
+        SymbolName {
+            base: rest.to_string(),
+            objc,
+            blocks,
+            cold,
+        }
+    }
+
+    // Whether the symbol (`self`, as found in the binary) is the `wanted` one.
+    // The base name is compared both as is and demangled.
+    fn matches(&self, wanted: &SymbolName, demangler: &mut Demangler) -> bool {
+        if self.blocks != wanted.blocks || self.cold != wanted.cold {
+            return false;
+        }
+
+        match (&self.objc, &wanted.objc) {
+            (Some(method), Some(wanted_method)) => method.matches(wanted_method),
+            (None, None) => {
+                self.base == wanted.base || demangler.demangle(&self.base) == wanted.base
+            }
+            _ => false,
+        }
+    }
+}
+
+impl ObjcMethodName {
+    // Categories are compared only when both names have one: symbols built
+    // from the ObjC metadata may not carry it
+    fn matches(&self, wanted: &ObjcMethodName) -> bool {
+        self.is_class_method == wanted.is_class_method
+            && self.class == wanted.class
+            && self.selector == wanted.selector
+            && match (&self.category, &wanted.category) {
+                (Some(category), Some(wanted_category)) => category == wanted_category,
+                _ => true,
+            }
+    }
+}
+
+// Symbols carry a leading underscore in the symbol table, atos drops it
+// (C++ "__Z..." and Swift "_$s..." become their usual mangled form)
+fn clean_symbol_name(name: &str) -> String {
+    name.strip_prefix('_').unwrap_or(name).to_string()
+}